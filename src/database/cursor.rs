@@ -0,0 +1,159 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::{Datetime, Thing};
+
+/// A position in a list ordered by a timestamp and then by record ID, newest first.
+///
+/// Cursors are handed out to clients as opaque strings, see [`Cursor::encode`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub time: Datetime,
+    pub id: Thing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorDirection {
+    /// Items older than the cursor, i.e. the next page.
+    After,
+    /// Items newer than the cursor, i.e. the previous page.
+    Before,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorPosition {
+    pub direction: CursorDirection,
+    pub cursor: Cursor,
+}
+
+/// A page of results along with the cursors to fetch the pages around it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CursorPage<T> {
+    pub data: Vec<T>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+/// Implemented by models that can be paginated with a [`Cursor`].
+pub trait Cursored {
+    fn cursor(&self) -> Option<Cursor>;
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.time.to_rfc3339(), self.id.to_raw());
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    /// Decode a cursor previously created with [`Cursor::encode`], returning `None` if it is invalid.
+    pub fn decode<S: AsRef<str>>(input: S) -> Option<Self> {
+        let raw = URL_SAFE_NO_PAD.decode(input.as_ref()).ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+        let (time, id) = raw.split_once('|')?;
+
+        let time = chrono::DateTime::parse_from_rfc3339(time).ok()?;
+        let id = Thing::try_from(id).ok()?;
+
+        Some(Self {
+            time: Datetime::from(time.to_utc()),
+            id,
+        })
+    }
+}
+
+impl CursorPosition {
+    /// Get the condition restricting a query to items past the cursor, ordered by `field`.
+    pub fn condition(&self, field: &str) -> String {
+        let operator = match self.direction {
+            CursorDirection::After => "<",
+            CursorDirection::Before => ">",
+        };
+
+        format!("({field} {operator} $cursor_time OR ({field} = $cursor_time AND id {operator} $cursor_id))")
+    }
+}
+
+/// Get the `ORDER BY` clause for a cursor paginated query over `field`.
+/// Pages before a cursor are fetched in ascending order, so the rows closest to the cursor come first.
+pub fn order_clause(field: &str, position: Option<&CursorPosition>) -> String {
+    match position.map(|position| position.direction) {
+        Some(CursorDirection::Before) => format!("ORDER BY {field} ASC, id ASC"),
+        _ => format!("ORDER BY {field} DESC, id DESC"),
+    }
+}
+
+impl<T: Cursored> CursorPage<T> {
+    /// Build a page from rows fetched with [`order_clause`] and a limit of `limit + 1`, the extra row telling us whether there is more.
+    pub fn new(mut rows: Vec<T>, limit: usize, position: Option<&CursorPosition>) -> Self {
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+
+        let direction = position.map(|position| position.direction);
+        if direction == Some(CursorDirection::Before) {
+            rows.reverse();
+        }
+
+        let first = rows.first().and_then(Cursored::cursor).map(|c| c.encode());
+        let last = rows.last().and_then(Cursored::cursor).map(|c| c.encode());
+
+        let (next, prev) = match direction {
+            None => (last.filter(|_| has_more), None),
+            Some(CursorDirection::After) => (last.filter(|_| has_more), first),
+            Some(CursorDirection::Before) => (last, first.filter(|_| has_more)),
+        };
+
+        Self {
+            data: rows,
+            next,
+            prev,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Row(i64);
+
+    impl Cursored for Row {
+        fn cursor(&self) -> Option<Cursor> {
+            Some(Cursor {
+                time: Datetime::from(chrono::DateTime::from_timestamp(self.0, 0).unwrap()),
+                id: Thing::from(("row", self.0.to_string().as_str())),
+            })
+        }
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = Row(1736364000).cursor().unwrap();
+        let decoded = Cursor::decode(cursor.encode()).expect("Failed to decode cursor");
+
+        assert_eq!(decoded, cursor);
+        assert_eq!(Cursor::decode("definitely not a cursor"), None);
+    }
+
+    #[test]
+    fn test_first_page() {
+        let page = CursorPage::new(vec![Row(5), Row(4), Row(3)], 2, None);
+
+        assert_eq!(page.data, vec![Row(5), Row(4)]);
+        assert_eq!(page.next, Row(4).cursor().map(|c| c.encode()));
+        assert_eq!(page.prev, None);
+    }
+
+    #[test]
+    fn test_page_before_cursor() {
+        let position = CursorPosition {
+            direction: CursorDirection::Before,
+            cursor: Row(3).cursor().unwrap(),
+        };
+        // Fetched in ascending order, closest to the cursor first.
+        let page = CursorPage::new(vec![Row(4), Row(5)], 2, Some(&position));
+
+        assert_eq!(page.data, vec![Row(5), Row(4)]);
+        assert_eq!(page.next, Row(4).cursor().map(|c| c.encode()));
+        assert_eq!(page.prev, None);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod cursor;
 pub mod db;
 pub mod models;
+pub mod stats;
@@ -0,0 +1,262 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use rust_decimal::Decimal;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Thing},
+    Surreal,
+};
+
+use super::{serialize_table, serialize_table_opt, transaction, wallet};
+use crate::errors::KromerError;
+
+/// Most points a balance series can have.
+const MAX_SERIES_POINTS: i64 = 1000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl Resolution {
+    fn step(&self) -> Duration {
+        match self {
+            Resolution::Hourly => Duration::hours(1),
+            Resolution::Daily => Duration::days(1),
+        }
+    }
+}
+
+/// The balance of a wallet at a point in time.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct BalancePoint {
+    pub time: DateTime<Utc>,
+    pub balance: Decimal,
+}
+
+/// The balance of a wallet recorded periodically, so past balances can be computed from the
+/// transactions since the snapshot instead of all of them.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
+    pub wallet: Thing,
+    pub balance: Decimal,
+    pub taken_at: Datetime,
+}
+
+impl Model {
+    /// Get the latest snapshot of a wallet taken at or before `time`.
+    pub async fn latest_before(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        time: DateTime<Utc>,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let q = "SELECT * FROM balance_snapshot WHERE wallet = $wallet AND taken_at <= $time ORDER BY taken_at DESC LIMIT 1;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("time", Datetime::from(time)))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Snapshot the balance of every wallet with transactions since `since`.
+    /// Returns how many snapshots were taken.
+    pub async fn take(db: &Surreal<Any>, since: DateTime<Utc>) -> Result<usize, surrealdb::Error> {
+        let q = r#"
+            LET $senders = SELECT VALUE from FROM transaction WHERE timestamp > $since;
+            LET $recipients = SELECT VALUE recipient FROM transaction WHERE timestamp > $since;
+            LET $wallets = array::filter(array::distinct(array::concat($senders, $recipients)), |$wallet| $wallet IS NOT NONE);
+            RETURN (INSERT INTO balance_snapshot (SELECT id AS wallet, balance, time::now() AS taken_at FROM $wallets)).len();
+        "#;
+
+        let mut response = db.query(q).bind(("since", Datetime::from(since))).await?;
+        let index = response.num_statements() - 1;
+        let count: Option<usize> = response.take(index)?;
+
+        Ok(count.unwrap_or_default())
+    }
+
+    /// The balance of a wallet at `time`. Starts from the latest snapshot before it if there is
+    /// one, otherwise works back from the current balance.
+    pub async fn balance_at(
+        db: &Surreal<Any>,
+        wallet: &wallet::Model,
+        time: DateTime<Utc>,
+    ) -> Result<Decimal, KromerError> {
+        let id = wallet
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+        match Self::latest_before(db, id.clone(), time).await? {
+            Some(snapshot) => {
+                let transactions =
+                    Self::transactions_between(db, &id, *snapshot.taken_at, time).await?;
+                Ok(snapshot.balance + Self::total_change(&transactions, &id))
+            }
+            None => {
+                let transactions = Self::transactions_between(db, &id, time, Utc::now()).await?;
+                Ok(wallet.balance - Self::total_change(&transactions, &id))
+            }
+        }
+    }
+
+    /// The balance of a wallet at every hour or day from `since` until `until`.
+    pub async fn series(
+        db: &Surreal<Any>,
+        wallet: &wallet::Model,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        resolution: Resolution,
+    ) -> Result<Vec<BalancePoint>, KromerError> {
+        let step = resolution.step();
+        let since = since
+            .duration_trunc(step)
+            .map_err(|e| KromerError::Validation(e.to_string()))?;
+        if until < since {
+            return Err(KromerError::Validation("until is before since".into()));
+        }
+        if (until - since).num_seconds() / step.num_seconds() >= MAX_SERIES_POINTS {
+            return Err(KromerError::Validation(format!(
+                "A balance series can have at most {MAX_SERIES_POINTS} points"
+            )));
+        }
+
+        let id = wallet
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+        let balance = Self::balance_at(db, wallet, since).await?;
+        let transactions = Self::transactions_between(db, &id, since, until).await?;
+
+        Ok(series_points(
+            balance,
+            &transactions,
+            &id,
+            since,
+            until,
+            step,
+        ))
+    }
+
+    fn total_change(transactions: &[transaction::Model], wallet: &Thing) -> Decimal {
+        transactions
+            .iter()
+            .map(|transaction| transaction.balance_change(wallet))
+            .sum()
+    }
+
+    /// Get the transactions that changed the balance of a wallet after `after` until `until`, oldest first.
+    /// Payments to a name count towards the wallet that owned it at the time, which is recorded as
+    /// the `recipient` of every transaction, and was backfilled from the name transfer history for
+    /// older ones by the `BackfillTransactionRecipient` migration.
+    async fn transactions_between(
+        db: &Surreal<Any>,
+        wallet: &Thing,
+        after: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<transaction::Model>, surrealdb::Error> {
+        let q = r#"
+            SELECT * FROM transaction
+            WHERE (from = $wallet OR recipient = $wallet) AND timestamp > $after AND timestamp <= $until
+            ORDER BY timestamp ASC;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet.clone()))
+            .bind(("after", Datetime::from(after)))
+            .bind(("until", Datetime::from(until)))
+            .await?;
+        let models: Vec<transaction::Model> = response.take(0)?;
+
+        Ok(models)
+    }
+}
+
+/// Build a series of balances at `since` and every `step` after it until `until`, from the
+/// balance at `since` and the transactions after it, oldest first.
+fn series_points(
+    mut balance: Decimal,
+    transactions: &[transaction::Model],
+    wallet: &Thing,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    step: Duration,
+) -> Vec<BalancePoint> {
+    let mut points = vec![BalancePoint {
+        time: since,
+        balance,
+    }];
+    let mut transactions = transactions.iter().peekable();
+
+    let mut time = since + step;
+    while time <= until {
+        while let Some(transaction) =
+            transactions.next_if(|transaction| *transaction.timestamp <= time)
+        {
+            balance += transaction.balance_change(wallet);
+        }
+        points.push(BalancePoint { time, balance });
+        time += step;
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::transactions::TransactionType;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_series_points() {
+        let wallet = Thing::from(("wallet", "alice"));
+        let other = Thing::from(("wallet", "bob"));
+        let since = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let transaction = |hours: i64, from: &Thing, to: &Thing| transaction::Model {
+            id: None,
+            amount: dec!(5),
+            from: from.clone(),
+            metadata: None,
+            timestamp: Datetime::from(since + Duration::minutes(hours * 60 + 30)),
+            to: to.clone(),
+            transaction_type: TransactionType::Transfer,
+            name: None,
+            refund_of: None,
+            recipient: None,
+            fee_of: None,
+        };
+        let transactions = vec![
+            transaction(0, &other, &wallet),
+            transaction(0, &other, &wallet),
+            transaction(2, &wallet, &other),
+        ];
+
+        let points = series_points(
+            dec!(100),
+            &transactions,
+            &wallet,
+            since,
+            since + Duration::hours(3),
+            Duration::hours(1),
+        );
+        let balances: Vec<Decimal> = points.iter().map(|point| point.balance).collect();
+
+        assert_eq!(balances, vec![dec!(100), dec!(110), dec!(110), dec!(105)]);
+        assert_eq!(points[3].time, since + Duration::hours(3));
+    }
+}
@@ -0,0 +1,279 @@
+use std::env;
+
+use chrono::{Duration, Utc};
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Id, Thing},
+    Surreal,
+};
+
+use super::{serialize_table, serialize_table_opt, serialize_table_vec, transaction, wallet};
+use crate::errors::{
+    hold::HoldError, transaction::TransactionError, wallet::WalletError, KromerError,
+};
+use crate::fees::FEE_SCHEDULE;
+use crate::routes::PaginationParams;
+
+/// The longest a hold can reserve funds for.
+static HOLD_MAX_TTL: Lazy<Duration> = Lazy::new(|| {
+    let hours = env::var("HOLD_MAX_TTL_HOURS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(168);
+    Duration::hours(hours)
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldStatus {
+    Active,
+    Captured,
+    Released,
+    Expired,
+}
+
+/// Funds reserved on a wallet for a holder, who can capture them or release them until the hold expires.
+/// Active holds reduce the available balance of the wallet.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    /// The wallet the funds are reserved on.
+    #[serde(serialize_with = "serialize_table")]
+    pub wallet: Thing,
+    /// The wallet that can capture the funds.
+    #[serde(serialize_with = "serialize_table")]
+    pub holder: Thing,
+    pub amount: Decimal,
+    /// How much of the hold was captured so far.
+    pub captured: Decimal,
+    pub status: HoldStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    pub created_at: Datetime,
+    pub expires_at: Datetime,
+    /// The transfers made by captures of the hold.
+    #[serde(default, serialize_with = "serialize_table_vec")]
+    pub transactions: Vec<Thing>,
+}
+
+impl Model {
+    /// Get a hold from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let thing = Thing::from(("hold", Id::from(id.as_ref())));
+        let q = "SELECT * FROM hold WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the holds on a wallet or held by it, newest first.
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * FROM hold WHERE wallet = $wallet OR holder = $wallet ORDER BY created_at DESC LIMIT $limit START $offset;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Mark active holds past their expiry as expired. Expired holds no longer count towards the
+    /// available balance even before this runs.
+    pub async fn expire_stale(db: &Surreal<Any>) -> Result<(), surrealdb::Error> {
+        let q = "UPDATE hold SET status = 'expired' WHERE status = 'active' AND expires_at <= time::now();";
+        db.query(q).await?.check()?;
+
+        Ok(())
+    }
+
+    /// Reserve funds on a wallet for a holder, expiring after the given number of seconds.
+    pub async fn create(
+        db: &Surreal<Any>,
+        wallet: &wallet::Model,
+        holder: &wallet::Model,
+        amount: Decimal,
+        expires_in: i64,
+        metadata: Option<String>,
+    ) -> Result<Model, KromerError> {
+        if amount <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+        // Bounded before building the duration, which panics for very large values.
+        if expires_in <= 0 || expires_in > HOLD_MAX_TTL.num_seconds() {
+            return Err(KromerError::Validation("Invalid hold expiry".into()));
+        }
+        let ttl = Duration::seconds(expires_in);
+
+        wallet.ensure_can_send()?;
+        holder.ensure_can_receive()?;
+
+        if wallet.available_balance() < amount {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        // The available balance is checked again inside the transaction in case it changed in the meantime.
+        let q = r#"
+            BEGIN TRANSACTION;
+            IF $wallet.available < $amount { THROW "Insufficient funds" };
+            LET $created = CREATE ONLY hold CONTENT {
+                wallet: $wallet,
+                holder: $holder,
+                amount: $amount,
+                metadata: $metadata,
+                expires_at: $expires_at,
+            };
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#;
+
+        let expires_at = Datetime::from(Utc::now() + ttl);
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet.id.clone()))
+            .bind(("holder", holder.id.clone()))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .bind(("expires_at", expires_at))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Internal("Unable to get created hold"))
+    }
+
+    /// Capture all or part of a hold, transferring it to the holder. What remains of a partially
+    /// captured hold stays reserved until it is captured, released or expires. The transfer fee is
+    /// charged like for any transfer, from the funds of the wallet that are not reserved.
+    /// Returns the updated hold and the transfer.
+    pub async fn capture(
+        db: &Surreal<Any>,
+        hold: &Model,
+        amount: Option<Decimal>,
+        metadata: Option<String>,
+    ) -> Result<(Model, transaction::Model), KromerError> {
+        hold.ensure_active()?;
+
+        let remaining = hold.amount - hold.captured;
+        let amount = amount.unwrap_or(remaining);
+        if amount <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+        if amount > remaining {
+            return Err(KromerError::Hold(HoldError::ExceedsRemaining));
+        }
+
+        let wallet = wallet::Model::get(db, hold.wallet.to_raw())
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+        let holder = wallet::Model::get(db, hold.holder.to_raw())
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+        wallet.ensure_can_send()?;
+        holder.ensure_can_receive()?;
+
+        let fee = FEE_SCHEDULE.quote(db, &wallet, &holder, amount).await?;
+        let fee_amount = fee.as_ref().map_or(Decimal::ZERO, |fee| fee.amount);
+        if wallet.available_balance() < fee_amount {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        // The captured funds are already reserved, so only the hold itself and the fee have to be checked.
+        let q = r#"
+            BEGIN TRANSACTION;
+            IF $fee > 0 AND $wallet.available < $fee { THROW "Insufficient funds" };
+            LET $hold = (UPDATE $id SET
+                status = IF captured + $amount >= amount { 'captured' } ELSE { 'active' },
+                captured += $amount
+            WHERE status = 'active' AND expires_at > time::now() AND amount - captured >= $amount
+            RETURN AFTER)[0];
+            IF $hold IS NONE { THROW "Hold is no longer active" };
+            LET $created = CREATE ONLY transaction CONTENT {
+                from: $hold.wallet,
+                to: $hold.holder,
+                amount: $amount,
+                metadata: $metadata,
+                transaction_type: 'transfer',
+            };
+            IF $treasury {
+                CREATE transaction CONTENT { from: $hold.wallet, to: $treasury, amount: $fee, metadata: 'type=fee;ref=' + <string> $created.id, transaction_type: 'fee', fee_of: $created.id };
+            };
+            UPDATE $id SET transactions += $created.id;
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", hold.id.clone()))
+            .bind(("wallet", hold.wallet.clone()))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .bind(("fee", fee_amount))
+            .bind(("treasury", fee.map(|fee| fee.treasury.id)))
+            .await?;
+        let index = response.num_statements() - 1;
+        let transaction: Option<transaction::Model> = response.take(index)?;
+        let transaction =
+            transaction.ok_or(KromerError::Transaction(TransactionError::FailedCreate))?;
+
+        let id = hold
+            .id
+            .as_ref()
+            .map(|id| id.id.to_raw())
+            .unwrap_or_default();
+        let hold = Self::get_partial(db, id)
+            .await?
+            .ok_or(KromerError::Hold(HoldError::NotFound))?;
+
+        Ok((hold, transaction))
+    }
+
+    /// Release what remains of a hold, making the funds available again.
+    pub async fn release(db: &Surreal<Any>, hold: &Model) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET status = 'released' WHERE status = 'active' RETURN AFTER)[0];";
+
+        let mut response = db.query(q).bind(("id", hold.id.clone())).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Hold(HoldError::NotActive))
+    }
+
+    fn ensure_active(&self) -> Result<(), HoldError> {
+        if self.status != HoldStatus::Active {
+            return Err(HoldError::NotActive);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use chrono::{Duration, Utc};
+use rust_decimal::Decimal;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Id, Thing},
+    Surreal,
+};
+
+use super::transaction::{refund_metadata, CommonMeta, Model as Transaction};
+use super::{serialize_table, serialize_table_opt, wallet};
+use crate::errors::{
+    invoice::InvoiceError, transaction::TransactionError, wallet::WalletError, KromerError,
+};
+use crate::models::transactions::TransactionType;
+use crate::routes::PaginationParams;
+
+/// The longest an invoice can stay open for.
+const INVOICE_MAX_EXPIRY_DAYS: i64 = 365;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    Unpaid,
+    Paid,
+    Expired,
+    Cancelled,
+}
+
+/// A payment request from a merchant wallet. Paid by a transfer with `invoice=<id>` in its metadata.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    /// The wallet the invoice has to be paid to.
+    #[serde(serialize_with = "serialize_table")]
+    pub wallet: Thing,
+    pub amount: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    pub status: InvoiceStatus,
+    pub created_at: Datetime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<Datetime>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub paid_by: Option<Thing>,
+    /// The transaction that paid the invoice.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub transaction: Option<Thing>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paid_at: Option<Datetime>,
+    /// Refunds of payments that didn't settle the invoice which couldn't be made yet, retried by
+    /// the scheduler until they succeed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_refunds: Vec<FailedRefund>,
+}
+
+/// A refund owed to the sender of a payment that didn't settle an invoice.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct FailedRefund {
+    #[serde(serialize_with = "serialize_table")]
+    pub payment: Thing,
+    /// Why the payment is refunded.
+    pub reason: String,
+    /// Why the last attempt at the refund failed.
+    pub error: String,
+    pub failed_at: Datetime,
+}
+
+/// The outcome of a payment made for an invoice.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settlement {
+    pub invoice: Model,
+    /// The refund of the payment, if it didn't settle the invoice.
+    pub refund: Option<Transaction>,
+    /// Set if the payment didn't settle the invoice and refunding it failed. The refund is then
+    /// recorded on the invoice and retried later.
+    pub refund_failed: bool,
+}
+
+impl Model {
+    /// Get an invoice from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let thing = Thing::from(("invoice", Id::from(id.as_ref())));
+        let q = "SELECT * FROM invoice WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the invoices of a wallet, newest first.
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * FROM invoice WHERE wallet = $wallet ORDER BY created_at DESC LIMIT $limit START $offset;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Mark unpaid invoices past their expiry as expired.
+    pub async fn expire_stale(db: &Surreal<Any>) -> Result<(), surrealdb::Error> {
+        let q = "UPDATE invoice SET status = 'expired' WHERE status = 'unpaid' AND expires_at < time::now();";
+        db.query(q).await?.check()?;
+
+        Ok(())
+    }
+
+    /// Create an invoice for a wallet, optionally expiring after the given number of seconds.
+    pub async fn create(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        amount: Decimal,
+        memo: Option<String>,
+        expires_in: Option<i64>,
+    ) -> Result<Model, KromerError> {
+        if amount <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+        if expires_in
+            .is_some_and(|seconds| seconds <= 0 || seconds > INVOICE_MAX_EXPIRY_DAYS * 24 * 60 * 60)
+        {
+            return Err(KromerError::Validation("Invalid invoice expiry".into()));
+        }
+
+        let expires_at = match expires_in {
+            Some(seconds) => Some(Datetime::from(
+                Utc::now()
+                    .checked_add_signed(Duration::seconds(seconds))
+                    .ok_or_else(|| KromerError::Validation("Invalid invoice expiry".into()))?,
+            )),
+            None => None,
+        };
+        let q = r#"
+            CREATE ONLY invoice CONTENT {
+                wallet: $wallet,
+                amount: $amount,
+                memo: $memo,
+                status: 'unpaid',
+                expires_at: $expires_at,
+            };
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("amount", amount))
+            .bind(("memo", memo))
+            .bind(("expires_at", expires_at))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Internal("Unable to get created invoice"))
+    }
+
+    /// Cancel an unpaid invoice.
+    pub async fn cancel(db: &Surreal<Any>, invoice: &Model) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET status = 'cancelled' WHERE status = 'unpaid' RETURN AFTER)[0];";
+
+        let mut response = db.query(q).bind(("id", invoice.id.clone())).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Invoice(InvoiceError::NotUnpaid))
+    }
+
+    /// Match a transfer to the invoice named in its metadata. A payment of the exact amount before
+    /// the expiry marks the invoice as paid, any other payment is refunded to the sender. If the
+    /// refund fails, it is recorded on the invoice to be retried with [`Model::retry_refunds`].
+    /// Returns `None` if the transfer wasn't for an invoice of its recipient.
+    pub async fn settle_payment(
+        db: &Surreal<Any>,
+        transaction: &Transaction,
+    ) -> Result<Option<Settlement>, KromerError> {
+        if transaction.transaction_type != TransactionType::Transfer {
+            return Ok(None);
+        }
+        let meta = CommonMeta::parse(transaction.metadata.as_deref().unwrap_or_default());
+        let Some(id) = meta.get("invoice") else {
+            return Ok(None);
+        };
+        let Some(invoice) = Self::get_partial(db, id).await? else {
+            return Ok(None);
+        };
+        if invoice.wallet != transaction.to {
+            return Ok(None);
+        }
+
+        let q = r#"
+            (UPDATE $id SET
+                status = 'paid',
+                paid_by = $paid_by,
+                transaction = $transaction,
+                paid_at = time::now()
+            WHERE status = 'unpaid' AND amount = $amount AND (expires_at IS NONE OR expires_at > time::now())
+            RETURN AFTER)[0];
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", invoice.id.clone()))
+            .bind(("paid_by", transaction.from.clone()))
+            .bind(("transaction", transaction.id.clone()))
+            .bind(("amount", transaction.amount))
+            .await?;
+        let paid: Option<Model> = response.take(0)?;
+
+        if let Some(invoice) = paid {
+            return Ok(Some(Settlement {
+                invoice,
+                refund: None,
+                refund_failed: false,
+            }));
+        }
+
+        // Read the invoice again, it may have been paid or expired in the meantime
+        let invoice = Self::get_partial(db, id)
+            .await?
+            .ok_or(KromerError::Invoice(InvoiceError::NotFound))?;
+        let reason = match invoice.status {
+            InvoiceStatus::Paid => "Invoice was already paid".to_string(),
+            InvoiceStatus::Expired => "Invoice has expired".to_string(),
+            InvoiceStatus::Cancelled => "Invoice was cancelled".to_string(),
+            InvoiceStatus::Unpaid => format!(
+                "Invoice is for {}, but {} was paid",
+                invoice.amount, transaction.amount
+            ),
+        };
+
+        match invoice.refund(db, transaction, &reason).await {
+            Ok(refund) => Ok(Some(Settlement {
+                invoice,
+                refund: Some(refund),
+                refund_failed: false,
+            })),
+            Err(e) => {
+                let invoice = invoice
+                    .record_failed_refund(db, transaction, &reason, &e.to_string())
+                    .await?;
+
+                Ok(Some(Settlement {
+                    invoice,
+                    refund: None,
+                    refund_failed: true,
+                }))
+            }
+        }
+    }
+
+    /// Retry the refunds that failed earlier. Returns the invoices with a refund that succeeded
+    /// this time, with the refund.
+    pub async fn retry_refunds(
+        db: &Surreal<Any>,
+    ) -> Result<Vec<(Model, Transaction)>, KromerError> {
+        let q = "SELECT * FROM invoice WHERE array::len(failed_refunds) > 0;";
+
+        let mut response = db.query(q).await?;
+        let invoices: Vec<Model> = response.take(0)?;
+
+        let mut refunded = Vec::new();
+        for invoice in invoices {
+            for failed in &invoice.failed_refunds {
+                let Some(payment) = Transaction::get(db, failed.payment.to_raw()).await? else {
+                    invoice.clear_failed_refund(db, &failed.payment).await?;
+                    continue;
+                };
+                // Someone may have refunded the payment by hand in the meantime.
+                if Transaction::refunded_amount(db, &payment).await? >= payment.amount {
+                    invoice.clear_failed_refund(db, &failed.payment).await?;
+                    continue;
+                }
+
+                match invoice.refund(db, &payment, &failed.reason).await {
+                    Ok(refund) => {
+                        let invoice = invoice.clear_failed_refund(db, &failed.payment).await?;
+                        refunded.push((invoice, refund));
+                    }
+                    Err(e) => {
+                        invoice
+                            .record_failed_refund(db, &payment, &failed.reason, &e.to_string())
+                            .await?;
+                    }
+                }
+            }
+        }
+
+        Ok(refunded)
+    }
+
+    /// Record that refunding a payment failed, replacing an earlier failure of the same refund.
+    async fn record_failed_refund(
+        &self,
+        db: &Surreal<Any>,
+        payment: &Transaction,
+        reason: &str,
+        error: &str,
+    ) -> Result<Model, KromerError> {
+        let q = r#"
+            (UPDATE $id SET failed_refunds = array::push(
+                failed_refunds[WHERE payment != $payment],
+                { payment: $payment, reason: $reason, error: $error, failed_at: time::now() }
+            ) RETURN AFTER)[0];
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", self.id.clone()))
+            .bind(("payment", payment.id.clone()))
+            .bind(("reason", reason.to_string()))
+            .bind(("error", error.to_string()))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Invoice(InvoiceError::NotFound))
+    }
+
+    /// Remove a refund from the failed refunds of the invoice once it is no longer owed.
+    async fn clear_failed_refund(
+        &self,
+        db: &Surreal<Any>,
+        payment: &Thing,
+    ) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET failed_refunds = failed_refunds[WHERE payment != $payment] RETURN AFTER)[0];";
+
+        let mut response = db
+            .query(q)
+            .bind(("id", self.id.clone()))
+            .bind(("payment", payment.clone()))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Invoice(InvoiceError::NotFound))
+    }
+
+    /// Send a payment that didn't settle the invoice back to its sender, or its `return` target.
+    async fn refund(
+        &self,
+        db: &Surreal<Any>,
+        transaction: &Transaction,
+        reason: &str,
+    ) -> Result<Transaction, KromerError> {
+        let merchant = wallet::Model::get(db, self.wallet.to_raw())
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+        let (target, name_target) = transaction.refund_target(db).await?;
+
+        let refunded = transaction
+            .id
+            .as_ref()
+            .ok_or(KromerError::Internal("Transaction is missing its id"))?;
+        let invoice_id = self
+            .id
+            .as_ref()
+            .map(|id| id.id.to_raw())
+            .unwrap_or_default();
+        let metadata = refund_metadata(
+            name_target.as_deref(),
+            refunded,
+            &[("invoice", &invoice_id), ("error", reason)],
+        );
+
+        Transaction::refund(
+            db,
+            transaction,
+            &merchant,
+            &target,
+            transaction.amount,
+            metadata,
+        )
+        .await
+    }
+}
@@ -1,8 +1,16 @@
+pub mod balance_snapshot;
+pub mod hold;
+pub mod invoice;
 pub mod name;
+pub mod name_listing;
+pub mod pending_transfer;
 pub mod player;
+pub mod standing_order;
 pub mod transaction;
 pub mod wallet;
+pub mod webhook;
 
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize, Serializer};
 use surrealdb::sql::Thing;
 
@@ -11,6 +19,11 @@ pub struct CountResponse {
     pub count: usize,
 }
 
+#[derive(Debug, Default, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct SupplyResponse {
+    pub supply: Decimal,
+}
+
 pub fn serialize_table<S>(x: &Thing, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -19,6 +32,13 @@ where
     s.serialize_str(&raw)
 }
 
+pub fn serialize_table_vec<S>(x: &[Thing], s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.collect_seq(x.iter().map(|thing| thing.to_raw()))
+}
+
 pub fn serialize_table_opt<S>(x: &Option<Thing>, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
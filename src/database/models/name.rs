@@ -1,12 +1,55 @@
+use std::env;
+
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use surrealdb::{
     engine::any::Any,
     sql::{Datetime, Id, Thing},
     Surreal,
 };
 
-use super::{serialize_table_opt, CountResponse};
+use super::{
+    name_listing::Model as NameListing, serialize_table, serialize_table_opt,
+    wallet::Model as Wallet, CountResponse,
+};
+use crate::database::cursor::{order_clause, Cursor, CursorPage, CursorPosition, Cursored};
+use crate::errors::{name::NameError, transaction::TransactionError, KromerError};
 use crate::routes::PaginationParams;
 
+static NAME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-z0-9]{1,64}$").unwrap());
+
+/// The amount a wallet has to pay to register a name.
+pub const NAME_COST: Decimal = dec!(500);
+
+/// How long a registration or renewal lasts. Names never expire if this is not set.
+pub static NAME_RENT_PERIOD: Lazy<Option<Duration>> = Lazy::new(|| {
+    env::var("NAME_RENT_DAYS")
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .map(Duration::days)
+});
+
+/// The amount the owner of a name has to pay to renew it.
+pub static NAME_RENEWAL_COST: Lazy<Decimal> = Lazy::new(|| {
+    env::var("NAME_RENEWAL_COST")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(NAME_COST)
+});
+
+/// How long an expired name can still be renewed by its owner before it is released.
+pub static NAME_GRACE_PERIOD: Lazy<Duration> = Lazy::new(|| {
+    let days = env::var("NAME_GRACE_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(7);
+    Duration::days(days)
+});
+
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Model {
     #[serde(
@@ -22,8 +65,33 @@ pub struct Model {
         serialize_with = "serialize_table_opt"
     )]
     pub original_owner: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
     pub owner: Thing,
     pub registered: Datetime,
+    /// When the name has to be renewed by, it never does if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<Datetime>,
+    /// Whether the owner was notified that the name expired.
+    #[serde(default)]
+    pub expired: bool,
+}
+
+/// What happened to a name during a sweep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepResult {
+    /// Names that expired and entered their grace period.
+    pub expired: Vec<Model>,
+    /// Names that were not renewed during their grace period and can be registered again.
+    pub released: Vec<Model>,
+    /// Listings that were cancelled because their name was released.
+    pub unlisted: Vec<NameListing>,
+}
+
+/// A name whose owner changed, with the listings of it that were cancelled because of that.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+struct OwnerChanged<T> {
+    name: T,
+    unlisted: Vec<NameListing>,
 }
 
 impl Model {
@@ -107,6 +175,32 @@ impl Model {
         Ok(models)
     }
 
+    /// Get a page of names, most recently registered first.
+    pub async fn page(
+        db: &Surreal<Any>,
+        limit: u64,
+        position: Option<&CursorPosition>,
+    ) -> Result<CursorPage<Model>, surrealdb::Error> {
+        let condition = position
+            .map(|position| format!("WHERE {}", position.condition("registered")))
+            .unwrap_or_default();
+        let q = format!(
+            "SELECT * FROM name {condition} {} LIMIT $limit;",
+            order_clause("registered", position)
+        );
+
+        let cursor = position.map(|position| position.cursor.clone());
+        let mut response = db
+            .query(q)
+            .bind(("limit", limit + 1))
+            .bind(("cursor_time", cursor.as_ref().map(|c| c.time.clone())))
+            .bind(("cursor_id", cursor.map(|c| c.id)))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(CursorPage::new(models, limit as usize, position))
+    }
+
     /// Get the total amount of transactions in the database
     pub async fn count(db: &Surreal<Any>) -> Result<usize, surrealdb::Error> {
         let q = "(SELECT count() FROM name GROUP BY count)[0] or { count: 0 }";
@@ -117,4 +211,339 @@ impl Model {
 
         Ok(count.count)
     }
+
+    /// Get all names owned by a wallet, omitting id.
+    pub async fn get_by_owner(
+        db: &Surreal<Any>,
+        owner: Thing,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * OMIT id FROM name WHERE owner = $owner ORDER BY name LIMIT $limit START $offset";
+
+        let mut response = db
+            .query(q)
+            .bind(("owner", owner))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Get the amount of names owned by a wallet
+    pub async fn count_by_owner(
+        db: &Surreal<Any>,
+        owner: Thing,
+    ) -> Result<usize, surrealdb::Error> {
+        let q = "(SELECT count() FROM name WHERE owner = $owner GROUP BY count)[0] or { count: 0 }";
+
+        let mut response = db.query(q).bind(("owner", owner)).await?;
+        let count: Option<CountResponse> = response.take(0)?;
+        let count = count.unwrap_or_default();
+
+        Ok(count.count)
+    }
+
+    /// Register a new name for a wallet, charging it [`NAME_COST`] through a `name_purchase` transaction.
+    pub async fn register<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        owner: &Wallet,
+        name: S,
+    ) -> Result<Model, KromerError> {
+        let name = name.as_ref().trim().to_lowercase();
+        if !NAME_REGEX.is_match(&name) {
+            return Err(KromerError::Name(NameError::InvalidName(name)));
+        }
+
+        owner.ensure_can_send()?;
+
+        if owner.available_balance() < NAME_COST {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        if Self::get_partial(db, &name).await?.is_some() {
+            return Err(KromerError::Name(NameError::Taken(name)));
+        }
+
+        let owner_id = owner
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+        // The available balance is checked again inside the transaction in case it changed in the meantime.
+        let q = r#"
+            BEGIN TRANSACTION;
+            IF $owner.available < $cost { THROW "Insufficient funds" };
+            LET $created = CREATE ONLY $id CONTENT { name: $name, owner: $owner, original_owner: $owner, expires_at: $expires_at };
+            CREATE transaction CONTENT { from: $owner, to: $id, amount: $cost, transaction_type: 'name_purchase', name: $id };
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", Self::thing(&name)))
+            .bind(("name", name.clone()))
+            .bind(("owner", owner_id))
+            .bind(("cost", NAME_COST))
+            .bind(("expires_at", Self::rent_expiry(Utc::now())))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        // If someone else registered the name in the meantime, creating the record fails and so does the transaction.
+        model.ok_or(KromerError::Internal("Unable to get registered name"))
+    }
+
+    /// Transfer a name from its current owner to another wallet, recording a `name_transfer` transaction.
+    /// Returns the transferred name and its listings, which were cancelled by the transfer.
+    pub async fn transfer(
+        db: &Surreal<Any>,
+        name: &Model,
+        from: &Wallet,
+        to: &Wallet,
+    ) -> Result<(Model, Vec<NameListing>), KromerError> {
+        if from.id.as_ref() != Some(&name.owner) {
+            return Err(KromerError::Name(NameError::NotOwner(name.name.clone())));
+        }
+
+        if name.unpaid() > 0 {
+            return Err(KromerError::Name(NameError::Expired(name.name.clone())));
+        }
+
+        from.ensure_can_send()?;
+        to.ensure_can_receive()?;
+
+        let q = r#"
+            BEGIN TRANSACTION;
+            LET $listings = SELECT VALUE id FROM name_listing WHERE name = $id AND status = 'active';
+            LET $updated = (UPDATE $id SET owner = $to, last_transfered = time::now(), last_updated = time::now() WHERE owner = $from RETURN AFTER)[0];
+            IF !$updated { THROW "Name owner changed during transfer" };
+            CREATE transaction CONTENT { from: $from, to: $to, amount: 0dec, transaction_type: 'name_transfer', name: $id };
+            RETURN { name: $updated, unlisted: (SELECT * FROM $listings) };
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", Self::thing(&name.name)))
+            .bind(("from", name.owner.clone()))
+            .bind(("to", to.id.clone()))
+            .await?;
+        let index = response.num_statements() - 1;
+        let transferred: Option<OwnerChanged<Model>> = response.take(index)?;
+
+        transferred
+            .map(|transferred| (transferred.name, transferred.unlisted))
+            .ok_or(KromerError::Name(NameError::FailedTransfer))
+    }
+
+    /// Renew a name for another rent period, charging its owner [`NAME_RENEWAL_COST`] through a
+    /// `name_purchase` transaction. An expired name can be renewed until its grace period ends.
+    pub async fn renew(
+        db: &Surreal<Any>,
+        name: &Model,
+        owner: &Wallet,
+    ) -> Result<Model, KromerError> {
+        let Some(period) = *NAME_RENT_PERIOD else {
+            return Err(KromerError::Name(NameError::RentDisabled));
+        };
+        if owner.id.as_ref() != Some(&name.owner) {
+            return Err(KromerError::Name(NameError::NotOwner(name.name.clone())));
+        }
+
+        owner.ensure_can_send()?;
+
+        if owner.available_balance() < *NAME_RENEWAL_COST {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        // Renewals extend the current period, so renewing late doesn't make the grace period free.
+        let now = Utc::now();
+        let start = name
+            .expires_at
+            .as_ref()
+            .map_or(now, |expires_at| **expires_at);
+        let expires_at = Datetime::from(start + period);
+
+        let q = r#"
+            BEGIN TRANSACTION;
+            IF $owner.available < $cost { THROW "Insufficient funds" };
+            LET $updated = (UPDATE $id SET expires_at = $expires_at, expired = false, last_updated = time::now() WHERE owner = $owner RETURN AFTER)[0];
+            IF !$updated { THROW "Name owner changed during renewal" };
+            CREATE transaction CONTENT { from: $owner, to: $id, amount: $cost, metadata: 'type=renewal', transaction_type: 'name_purchase', name: $id };
+            RETURN $updated;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", Self::thing(&name.name)))
+            .bind(("owner", name.owner.clone()))
+            .bind(("cost", *NAME_RENEWAL_COST))
+            .bind(("expires_at", expires_at))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Internal("Unable to get renewed name"))
+    }
+
+    /// Get the names that expired and can still be renewed, the ones expiring first first.
+    pub async fn get_unpaid(db: &Surreal<Any>) -> Result<Vec<Model>, surrealdb::Error> {
+        let q = "SELECT * FROM name WHERE expires_at IS NOT NONE AND expires_at <= time::now() ORDER BY expires_at ASC;";
+
+        let mut response = db.query(q).await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Mark names past their expiry as expired and release names past their grace period.
+    /// Names registered before rent was enabled get a full rent period from now.
+    pub async fn sweep(db: &Surreal<Any>) -> Result<SweepResult, surrealdb::Error> {
+        let Some(period) = *NAME_RENT_PERIOD else {
+            return Ok(SweepResult {
+                expired: Vec::new(),
+                released: Vec::new(),
+                unlisted: Vec::new(),
+            });
+        };
+
+        // Released names are deleted so they can be registered again, but a `released_name` record
+        // is kept so transactions to them can still be traced to the wallet that owned them.
+        let q = r#"
+            UPDATE name SET expires_at = $expires_at WHERE expires_at IS NONE;
+            UPDATE name SET expired = true WHERE expired != true AND expires_at IS NOT NONE AND expires_at <= time::now() RETURN AFTER;
+            BEGIN TRANSACTION;
+            LET $listings = SELECT VALUE id FROM name_listing WHERE status = 'active' AND name IN (SELECT VALUE id FROM name WHERE expires_at IS NOT NONE AND expires_at <= $released_before);
+            LET $released = DELETE name WHERE expires_at IS NOT NONE AND expires_at <= $released_before RETURN BEFORE;
+            FOR $name IN $released {
+                CREATE released_name CONTENT { name: $name.name, owner: $name.owner, original_owner: $name.original_owner, registered: $name.registered };
+            };
+            RETURN { name: $released, unlisted: (SELECT * FROM $listings) };
+            COMMIT TRANSACTION;
+        "#;
+
+        let now = Utc::now();
+        let mut response = db
+            .query(q)
+            .bind(("expires_at", Datetime::from(now + period)))
+            .bind(("released_before", Datetime::from(now - *NAME_GRACE_PERIOD)))
+            .await?;
+        let index = response.num_statements() - 1;
+        let expired: Vec<Model> = response.take(1)?;
+        let released: Option<OwnerChanged<Vec<Model>>> = response.take(index)?;
+        let (released, unlisted) = released
+            .map(|released| (released.name, released.unlisted))
+            .unwrap_or_default();
+
+        Ok(SweepResult {
+            expired,
+            released,
+            unlisted,
+        })
+    }
+
+    /// Get the wallet that owns a name, or that owned it last if it was released.
+    pub async fn owner_of(
+        db: &Surreal<Any>,
+        name: &str,
+    ) -> Result<Option<Thing>, surrealdb::Error> {
+        let q = r#"
+            RETURN (SELECT VALUE owner FROM name WHERE name = $name)[0]
+                ?? (SELECT VALUE owner FROM released_name WHERE name = $name ORDER BY released_at DESC LIMIT 1)[0];
+        "#;
+
+        let mut response = db.query(q).bind(("name", name.to_string())).await?;
+        let owner: Option<Thing> = response.take(0)?;
+
+        Ok(owner)
+    }
+
+    /// Whole days left to renew an expired name before it is released, 0 if it hasn't expired.
+    pub fn unpaid(&self) -> i64 {
+        self.unpaid_at(Utc::now())
+    }
+
+    fn unpaid_at(&self, now: DateTime<Utc>) -> i64 {
+        let Some(expires_at) = &self.expires_at else {
+            return 0;
+        };
+        if **expires_at > now {
+            return 0;
+        }
+
+        let left = **expires_at + *NAME_GRACE_PERIOD - now;
+        // Round up, an expired name is unpaid for at least a day until it is released.
+        ((left.num_seconds() + 86_399) / 86_400).max(1)
+    }
+
+    /// When a name registered or renewed at `time` expires, if names expire at all.
+    fn rent_expiry(time: DateTime<Utc>) -> Option<Datetime> {
+        NAME_RENT_PERIOD.map(|period| Datetime::from(time + period))
+    }
+
+    /// Names are stored with their name as the record ID, e.g. `name:kromer`.
+    fn thing(name: &str) -> Thing {
+        Thing::from(("name", Id::from(name)))
+    }
+}
+
+impl Cursored for Model {
+    fn cursor(&self) -> Option<Cursor> {
+        Some(Cursor {
+            time: self.registered.clone(),
+            id: self.id.clone()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_expiring_at(expires_at: Option<DateTime<Utc>>) -> Model {
+        Model {
+            id: None,
+            last_transfered: None,
+            name: "kromer".into(),
+            original_owner: None,
+            owner: Thing::from(("wallet", "owner")),
+            registered: Datetime::default(),
+            expires_at: expires_at.map(Datetime::from),
+            expired: false,
+        }
+    }
+
+    #[test]
+    fn test_unpaid() {
+        let now = Utc::now();
+
+        assert_eq!(name_expiring_at(None).unpaid_at(now), 0);
+        assert_eq!(
+            name_expiring_at(Some(now + Duration::days(1))).unpaid_at(now),
+            0
+        );
+
+        let grace_days = NAME_GRACE_PERIOD.num_days();
+        assert_eq!(name_expiring_at(Some(now)).unpaid_at(now), grace_days);
+        assert_eq!(
+            name_expiring_at(Some(now - Duration::hours(36))).unpaid_at(now),
+            (grace_days - 1).max(1)
+        );
+        assert_eq!(
+            name_expiring_at(Some(now - *NAME_GRACE_PERIOD - Duration::days(1))).unpaid_at(now),
+            1
+        );
+    }
 }
@@ -0,0 +1,328 @@
+use chrono::{Duration, Utc};
+use rust_decimal::Decimal;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Id, Thing},
+    Surreal,
+};
+
+use super::{name, serialize_table, serialize_table_opt, transaction, wallet};
+use crate::errors::{
+    name::NameError, name_listing::NameListingError, transaction::TransactionError, KromerError,
+};
+use crate::fees::FEE_SCHEDULE;
+use crate::routes::PaginationParams;
+
+/// The longest a listing can stay active for.
+const LISTING_MAX_EXPIRY_DAYS: i64 = 365;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingStatus {
+    Active,
+    Sold,
+    Cancelled,
+    Expired,
+}
+
+/// A name offered for sale by its owner. Listings are cancelled by the `name_listing_cancel`
+/// database event when the name changes owner any other way.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
+    pub name: Thing,
+    #[serde(serialize_with = "serialize_table")]
+    pub seller: Thing,
+    pub price: Decimal,
+    /// The only wallet that can buy the name, anyone can if not set.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub buyer: Option<Thing>,
+    pub status: ListingStatus,
+    pub created_at: Datetime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<Datetime>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub sold_to: Option<Thing>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sold_at: Option<Datetime>,
+    /// The payment for the name.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub transaction: Option<Thing>,
+}
+
+impl Model {
+    /// Get a listing from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let thing = Thing::from(("name_listing", Id::from(id.as_ref())));
+        let q = "SELECT * FROM name_listing WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the active listings, newest first.
+    pub async fn get_active(
+        db: &Surreal<Any>,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * FROM name_listing WHERE status = 'active' ORDER BY created_at DESC LIMIT $limit START $offset;";
+
+        let mut response = db
+            .query(q)
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Get the active listing of a name, if it is for sale.
+    pub async fn get_active_for_name(
+        db: &Surreal<Any>,
+        name: Thing,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let q = "SELECT * FROM name_listing WHERE name = $name AND status = 'active' LIMIT 1;";
+
+        let mut response = db.query(q).bind(("name", name)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the listings a wallet is the seller or the target buyer of, newest first.
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        Self::expire_stale(db).await?;
+
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * FROM name_listing WHERE seller = $wallet OR buyer = $wallet OR sold_to = $wallet ORDER BY created_at DESC LIMIT $limit START $offset;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Mark active listings past their expiry as expired.
+    pub async fn expire_stale(db: &Surreal<Any>) -> Result<(), surrealdb::Error> {
+        let q = "UPDATE name_listing SET status = 'expired' WHERE status = 'active' AND expires_at IS NOT NONE AND expires_at <= time::now();";
+        db.query(q).await?.check()?;
+
+        Ok(())
+    }
+
+    /// List a name for sale by its owner, optionally only to one buyer and until it expires after
+    /// the given number of seconds. An earlier listing of the name is replaced.
+    pub async fn create(
+        db: &Surreal<Any>,
+        name: &name::Model,
+        seller: &wallet::Model,
+        price: Decimal,
+        buyer: Option<&wallet::Model>,
+        expires_in: Option<i64>,
+    ) -> Result<Model, KromerError> {
+        if price <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+        if expires_in
+            .is_some_and(|seconds| seconds <= 0 || seconds > LISTING_MAX_EXPIRY_DAYS * 24 * 60 * 60)
+        {
+            return Err(KromerError::Validation("Invalid listing expiry".into()));
+        }
+        if seller.id.as_ref() != Some(&name.owner) {
+            return Err(KromerError::Name(NameError::NotOwner(name.name.clone())));
+        }
+        if name.unpaid() > 0 {
+            return Err(KromerError::Name(NameError::Expired(name.name.clone())));
+        }
+        if buyer.is_some_and(|buyer| buyer.id == seller.id) {
+            return Err(KromerError::NameListing(NameListingError::OwnListing));
+        }
+
+        seller.ensure_can_send()?;
+
+        let expires_at = match expires_in {
+            Some(seconds) => Some(Datetime::from(
+                Utc::now()
+                    .checked_add_signed(Duration::seconds(seconds))
+                    .ok_or_else(|| KromerError::Validation("Invalid listing expiry".into()))?,
+            )),
+            None => None,
+        };
+        let q = r#"
+            BEGIN TRANSACTION;
+            UPDATE name_listing SET status = 'cancelled' WHERE name = $name AND status = 'active';
+            LET $created = CREATE ONLY name_listing CONTENT {
+                name: $name,
+                seller: $seller,
+                price: $price,
+                buyer: $buyer,
+                expires_at: $expires_at,
+            };
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("name", name.id.clone()))
+            .bind(("seller", seller.id.clone()))
+            .bind(("price", price))
+            .bind(("buyer", buyer.and_then(|buyer| buyer.id.clone())))
+            .bind(("expires_at", expires_at))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Internal("Unable to get created listing"))
+    }
+
+    /// Cancel an active listing.
+    pub async fn cancel(db: &Surreal<Any>, listing: &Model) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET status = 'cancelled' WHERE status = 'active' RETURN AFTER)[0];";
+
+        let mut response = db.query(q).bind(("id", listing.id.clone())).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::NameListing(NameListingError::NotActive))
+    }
+
+    /// Buy a listed name. In a single database transaction the buyer pays the seller with a
+    /// `transfer`, plus the transfer fee, and the name is handed over with a `name_transfer`.
+    /// Returns the sold listing, the payment and the name with its new owner.
+    pub async fn buy(
+        db: &Surreal<Any>,
+        listing: &Model,
+        buyer: &wallet::Model,
+    ) -> Result<(Model, transaction::Model, name::Model), KromerError> {
+        if listing.status != ListingStatus::Active {
+            return Err(KromerError::NameListing(NameListingError::NotActive));
+        }
+        if buyer.id.as_ref() == Some(&listing.seller) {
+            return Err(KromerError::NameListing(NameListingError::OwnListing));
+        }
+        if listing
+            .buyer
+            .as_ref()
+            .is_some_and(|target| buyer.id.as_ref() != Some(target))
+        {
+            return Err(KromerError::NameListing(NameListingError::NotForBuyer));
+        }
+
+        let name = name::Model::get(db, listing.name.to_raw())
+            .await?
+            .ok_or(KromerError::Name(NameError::NotFound))?;
+        if name.unpaid() > 0 {
+            return Err(KromerError::Name(NameError::Expired(name.name.clone())));
+        }
+        let seller = wallet::Model::get(db, listing.seller.to_raw())
+            .await?
+            .ok_or(KromerError::Internal("Listing seller does not exist"))?;
+
+        buyer.ensure_can_send()?;
+        buyer.ensure_can_receive()?;
+        seller.ensure_can_send()?;
+        seller.ensure_can_receive()?;
+
+        let fee = FEE_SCHEDULE
+            .quote(db, buyer, &seller, listing.price)
+            .await?;
+        let fee_amount = fee.as_ref().map_or(Decimal::ZERO, |fee| fee.amount);
+        if buyer.available_balance() < listing.price + fee_amount {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        // The listing is marked as sold before the name changes owner, so the `name_listing_cancel` event leaves it alone.
+        let q = r#"
+            BEGIN TRANSACTION;
+            LET $listing = (UPDATE $id SET status = 'sold', sold_to = $buyer, sold_at = time::now()
+                WHERE status = 'active' AND (expires_at IS NONE OR expires_at > time::now()) AND (buyer IS NONE OR buyer = $buyer)
+                RETURN AFTER)[0];
+            IF !$listing { THROW "Listing is no longer active" };
+            IF $buyer.available < $listing.price + $fee { THROW "Insufficient funds" };
+            LET $name = (UPDATE $listing.name SET owner = $buyer, last_transfered = time::now(), last_updated = time::now()
+                WHERE owner = $listing.seller
+                RETURN AFTER)[0];
+            IF !$name { THROW "Name owner changed during sale" };
+            LET $payment = CREATE ONLY transaction CONTENT { from: $buyer, to: $listing.seller, amount: $listing.price, metadata: 'type=name_sale;name=' + $name.name, transaction_type: 'transfer' };
+            IF $treasury {
+                CREATE transaction CONTENT { from: $buyer, to: $treasury, amount: $fee, metadata: 'type=fee;ref=' + <string> $payment.id, transaction_type: 'fee', fee_of: $payment.id };
+            };
+            CREATE transaction CONTENT { from: $listing.seller, to: $buyer, amount: 0dec, transaction_type: 'name_transfer', name: $listing.name };
+            UPDATE $id SET transaction = $payment.id;
+            RETURN $payment;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", listing.id.clone()))
+            .bind(("buyer", buyer.id.clone()))
+            .bind(("fee", fee_amount))
+            .bind(("treasury", fee.map(|fee| fee.treasury.id)))
+            .await?;
+        let index = response.num_statements() - 1;
+        let payment: Option<transaction::Model> = response.take(index)?;
+        let payment = payment.ok_or(KromerError::Transaction(TransactionError::FailedCreate))?;
+
+        let id = listing
+            .id
+            .as_ref()
+            .map(|id| id.id.to_raw())
+            .unwrap_or_default();
+        let listing = Self::get_partial(db, id)
+            .await?
+            .ok_or(KromerError::NameListing(NameListingError::NotFound))?;
+        let name = name::Model::get(db, listing.name.to_raw())
+            .await?
+            .ok_or(KromerError::Name(NameError::NotFound))?;
+
+        Ok((listing, payment, name))
+    }
+}
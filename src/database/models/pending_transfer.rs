@@ -0,0 +1,354 @@
+use std::env;
+
+use chrono::{Duration, Utc};
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Id, Thing},
+    Surreal,
+};
+
+use super::{serialize_table, serialize_table_opt, serialize_table_vec, transaction, wallet};
+use crate::errors::{
+    pending_transfer::PendingTransferError, transaction::TransactionError, wallet::WalletError,
+    KromerError,
+};
+use crate::routes::PaginationParams;
+
+/// How long a proposal stays open before it expires.
+static PENDING_TRANSFER_TTL: Lazy<Duration> = Lazy::new(|| {
+    let hours = env::var("PENDING_TRANSFER_TTL_HOURS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(24);
+    Duration::hours(hours)
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingTransferStatus {
+    Pending,
+    Executed,
+    Rejected,
+    Cancelled,
+    Expired,
+}
+
+/// A proposed transfer from a shared wallet, waiting for approval by its members.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
+    pub wallet: Thing,
+    #[serde(serialize_with = "serialize_table")]
+    pub to: Thing,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    #[serde(serialize_with = "serialize_table")]
+    pub proposer: Thing,
+    #[serde(serialize_with = "serialize_table_vec")]
+    pub approvals: Vec<Thing>,
+    #[serde(serialize_with = "serialize_table_vec")]
+    pub rejections: Vec<Thing>,
+    pub required_approvals: u32,
+    pub status: PendingTransferStatus,
+    pub created_at: Datetime,
+    pub expires_at: Datetime,
+    /// The transaction made once the transfer was executed.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub transaction: Option<Thing>,
+}
+
+impl Model {
+    /// Get a pending transfer from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let thing = Thing::from(("pending_transfer", Id::from(id.as_ref())));
+        let q = "SELECT * FROM pending_transfer WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the pending transfers of a wallet, newest first.
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * FROM pending_transfer WHERE wallet = $wallet ORDER BY created_at DESC LIMIT $limit START $offset;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Mark proposals that weren't decided in time as expired. Returns the expired proposals.
+    pub async fn expire_stale(db: &Surreal<Any>) -> Result<Vec<Model>, surrealdb::Error> {
+        let q = "UPDATE pending_transfer SET status = 'expired' WHERE status = 'pending' AND expires_at < time::now() RETURN AFTER;";
+
+        let mut response = db.query(q).await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Propose a transfer from a wallet with a multisig policy. The proposer counts as the first approval.
+    pub async fn propose(
+        db: &Surreal<Any>,
+        wallet: &wallet::Model,
+        to: &wallet::Model,
+        amount: Decimal,
+        metadata: Option<String>,
+        proposer: Thing,
+    ) -> Result<Model, KromerError> {
+        if amount <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+        wallet.ensure_can_send()?;
+        to.ensure_can_receive()?;
+
+        if wallet.available_balance() < amount {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        let policy = wallet.multisig.as_ref().ok_or(KromerError::Validation(
+            "Wallet has no multisig policy".into(),
+        ))?;
+
+        let q = r#"
+            CREATE ONLY pending_transfer CONTENT {
+                wallet: $wallet,
+                to: $to,
+                amount: $amount,
+                metadata: $metadata,
+                proposer: $proposer,
+                approvals: [$proposer],
+                rejections: [],
+                required_approvals: $required_approvals,
+                status: 'pending',
+                expires_at: $expires_at,
+            };
+        "#;
+
+        let expires_at = Datetime::from(Utc::now() + *PENDING_TRANSFER_TTL);
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet.id.clone()))
+            .bind(("to", to.id.clone()))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .bind(("proposer", proposer))
+            .bind(("required_approvals", policy.approvals))
+            .bind(("expires_at", expires_at))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Internal(
+            "Unable to get created pending transfer",
+        ))
+    }
+
+    /// Record the vote of a member. Returns the updated pending transfer.
+    pub async fn vote(
+        db: &Surreal<Any>,
+        pending: &Model,
+        member: Thing,
+        approve: bool,
+    ) -> Result<Model, KromerError> {
+        pending.ensure_pending()?;
+        if pending.approvals.contains(&member) || pending.rejections.contains(&member) {
+            return Err(KromerError::PendingTransfer(
+                PendingTransferError::AlreadyVoted,
+            ));
+        }
+
+        // Checked again in the query, so concurrent votes of the same member are only counted once.
+        let q = if approve {
+            "(UPDATE $id SET approvals += $member WHERE status = 'pending' AND expires_at > time::now() AND $member NOTIN approvals AND $member NOTIN rejections RETURN AFTER)[0];"
+        } else {
+            "(UPDATE $id SET rejections += $member WHERE status = 'pending' AND expires_at > time::now() AND $member NOTIN approvals AND $member NOTIN rejections RETURN AFTER)[0];"
+        };
+
+        let mut response = db
+            .query(q)
+            .bind(("id", pending.id.clone()))
+            .bind(("member", member))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+        if let Some(model) = model {
+            return Ok(model);
+        }
+
+        let id = pending
+            .id
+            .as_ref()
+            .map(|id| id.id.to_raw())
+            .unwrap_or_default();
+        let current = Self::get_partial(db, id)
+            .await?
+            .ok_or(KromerError::PendingTransfer(PendingTransferError::NotFound))?;
+        current.ensure_pending()?;
+
+        Err(KromerError::PendingTransfer(
+            PendingTransferError::AlreadyVoted,
+        ))
+    }
+
+    /// Reject the proposal once enough members rejected it that it can no longer be approved.
+    pub async fn reject_if_unreachable(
+        db: &Surreal<Any>,
+        pending: Model,
+        eligible_members: usize,
+    ) -> Result<Model, KromerError> {
+        let remaining = eligible_members.saturating_sub(pending.rejections.len());
+        if remaining >= pending.required_approvals as usize {
+            return Ok(pending);
+        }
+
+        Self::set_status(db, &pending, PendingTransferStatus::Rejected).await
+    }
+
+    /// Execute the transfer through the normal transfer path once enough members approved it.
+    /// Only approvals of players that are still owners or spenders of the wallet count, and the wallet has to still have
+    /// a multisig policy. If the transfer fails, e.g. because the wallet no longer has the funds, the proposal stays
+    /// pending.
+    pub async fn execute_if_approved(
+        db: &Surreal<Any>,
+        pending: Model,
+    ) -> Result<Model, KromerError> {
+        let wallet = wallet::Model::get(db, pending.wallet.to_raw())
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+        let members = wallet::Model::get_members(db, pending.wallet.clone()).await?;
+        let approvals = pending
+            .approvals
+            .iter()
+            .filter(|approval| {
+                members.iter().any(|member| {
+                    member.player == **approval && member.role != wallet::WalletRole::Viewer
+                })
+            })
+            .count();
+        if approvals < pending.required_approvals as usize {
+            return Ok(pending);
+        }
+        if wallet.multisig.is_none() {
+            return Err(KromerError::Validation(
+                "Wallet no longer has a multisig policy".into(),
+            ));
+        }
+
+        // Claim the proposal first, so concurrent approvals can't execute it twice.
+        let claimed = Self::set_status(db, &pending, PendingTransferStatus::Executed).await?;
+
+        let result = async {
+            let to = wallet::Model::get(db, claimed.to.to_raw())
+                .await?
+                .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+            transaction::Model::transfer(
+                db,
+                &wallet,
+                None,
+                &to,
+                claimed.amount,
+                claimed.metadata.clone(),
+            )
+            .await
+        }
+        .await;
+
+        match result {
+            Ok(transaction) => {
+                let q = "UPDATE ONLY $id SET transaction = $transaction RETURN AFTER;";
+                let mut response = db
+                    .query(q)
+                    .bind(("id", claimed.id.clone()))
+                    .bind(("transaction", transaction.id))
+                    .await?;
+                let model: Option<Model> = response.take(0)?;
+
+                model.ok_or(KromerError::PendingTransfer(PendingTransferError::NotFound))
+            }
+            Err(e) => {
+                let q = "UPDATE $id SET status = 'pending';";
+                db.query(q).bind(("id", claimed.id)).await?.check()?;
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Move a pending proposal to another status, failing if it was decided or expired in the meantime.
+    pub async fn set_status(
+        db: &Surreal<Any>,
+        pending: &Model,
+        status: PendingTransferStatus,
+    ) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET status = $status WHERE status = 'pending' AND expires_at > time::now() RETURN AFTER)[0];";
+
+        let mut response = db
+            .query(q)
+            .bind(("id", pending.id.clone()))
+            .bind(("status", status))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::PendingTransfer(
+            PendingTransferError::NotPending,
+        ))
+    }
+
+    /// Get the addresses to notify about a proposal: the wallet itself and the wallets owned by its members.
+    pub async fn notification_addresses(
+        db: &Surreal<Any>,
+        wallet: Thing,
+    ) -> Result<Vec<String>, surrealdb::Error> {
+        let q = r#"
+            LET $members = SELECT VALUE in FROM owns WHERE out = $wallet;
+            LET $owned = SELECT VALUE out.address FROM owns WHERE in IN $members AND (role ?? 'owner') = 'owner';
+            RETURN array::distinct(array::push($owned, $wallet.address));
+        "#;
+
+        let mut response = db.query(q).bind(("wallet", wallet)).await?;
+        let addresses: Option<Vec<String>> = response.take(2)?;
+
+        Ok(addresses.unwrap_or_default())
+    }
+
+    /// Proposals past their expiry count as decided, even before they are marked as expired.
+    fn ensure_pending(&self) -> Result<(), PendingTransferError> {
+        if self.status != PendingTransferStatus::Pending || *self.expires_at <= Utc::now() {
+            return Err(PendingTransferError::NotPending);
+        }
+
+        Ok(())
+    }
+}
@@ -51,12 +51,45 @@ impl Model {
         Ok(model)
     }
 
-    /// Get wallet from its name
+    /// Give the name of a player to the player with the given ID, renaming any other player still holding it
+    /// (case insensitively) after their ID. Names are unique, but a player who changed their name keeps the old one
+    /// until they log in again, by which time someone else may have taken it.
+    pub async fn release_name(
+        db: &Surreal<Any>,
+        id: Thing,
+        name: String,
+    ) -> Result<(), surrealdb::Error> {
+        let q = "UPDATE player SET name = record::id(id) WHERE name_lower = string::lowercase($name) AND id != $id;";
+
+        db.query(q)
+            .bind(("id", id))
+            .bind(("name", name))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Update the name of a player, e.g. after they changed their Minecraft username.
+    pub async fn set_name(
+        db: &Surreal<Any>,
+        id: Thing,
+        name: String,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let q = "UPDATE ONLY $id SET name = $name;";
+
+        let mut response = db.query(q).bind(("id", id)).bind(("name", name)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get a player from their name, ignoring case like Minecraft does
     pub async fn get_by_name(
         db: &Surreal<Any>,
         name: String,
     ) -> Result<Option<Model>, surrealdb::Error> {
-        let q = "SELECT * from wallet where name = $name;";
+        let q = "SELECT * FROM player WHERE name_lower = string::lowercase($name) LIMIT 1;";
 
         let mut response = db.query(q).bind(("name", name)).await?;
         let model: Option<Model> = response.take(0)?;
@@ -64,12 +97,12 @@ impl Model {
         Ok(model)
     }
 
-    /// Get wallet from its name, omitting id
+    /// Get a player from their name, omitting id
     pub async fn get_by_name_excl(
         db: &Surreal<Any>,
         name: String,
     ) -> Result<Option<Model>, surrealdb::Error> {
-        let q = "SELECT * OMIT id from wallet where name = $name;";
+        let q = "SELECT * OMIT id FROM player WHERE name_lower = string::lowercase($name) LIMIT 1;";
 
         let mut response = db.query(q).bind(("name", name)).await?;
         let model: Option<Model> = response.take(0)?;
@@ -77,6 +110,19 @@ impl Model {
         Ok(model)
     }
 
+    /// Get the players owning a wallet
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        let q = "SELECT * FROM $wallet<-owns<-player;";
+
+        let mut response = db.query(q).bind(("wallet", wallet)).await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
     /// Get all players, omitting id.
     pub async fn all(
         db: &Surreal<Any>,
@@ -86,7 +132,7 @@ impl Model {
         let offset = pagination.offset.unwrap_or(0);
         let limit = limit.clamp(1, 1000);
 
-        let q = "SELECT * OMIT id from wallet LIMIT $limit START $offset";
+        let q = "SELECT * OMIT id FROM player LIMIT $limit START $offset";
 
         let mut response = db
             .query(q)
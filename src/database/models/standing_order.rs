@@ -0,0 +1,647 @@
+use std::env;
+
+use chrono::{DateTime, Duration, Months, Utc};
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Id, Thing},
+    Surreal,
+};
+
+use super::{serialize_table, serialize_table_opt, serialize_table_vec, transaction, wallet};
+use crate::errors::{
+    standing_order::StandingOrderError, transaction::TransactionError, wallet::WalletError,
+    KromerError,
+};
+use crate::routes::PaginationParams;
+
+/// The shortest interval a standing order can run at.
+static STANDING_ORDER_MIN_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let minutes = env::var("STANDING_ORDER_MIN_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(60);
+    Duration::minutes(minutes)
+});
+
+/// Attempts at a payment of an order with the `retry` policy before it is skipped.
+static STANDING_ORDER_MAX_RETRIES: Lazy<i64> = Lazy::new(|| {
+    env::var("STANDING_ORDER_MAX_RETRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(3)
+});
+
+/// Time between attempts at a payment of an order with the `retry` policy.
+static STANDING_ORDER_RETRY_DELAY: Lazy<Duration> = Lazy::new(|| {
+    let minutes = env::var("STANDING_ORDER_RETRY_MINUTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(60);
+    Duration::minutes(minutes)
+});
+
+/// The longest interval a standing order can pay at, in years.
+const MAX_INTERVAL_YEARS: u32 = 10;
+
+/// How often a standing order pays, either a fixed duration like `30m`, `12h`, `1d` or `2w`, or a
+/// number of calendar months like `1mo`. The cron macros `@hourly`, `@daily`, `@weekly`,
+/// `@monthly` and `@yearly` are accepted too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interval {
+    Every(Duration),
+    Months(u32),
+}
+
+impl Interval {
+    pub fn parse(value: &str) -> Result<Interval, StandingOrderError> {
+        let value = value.trim().to_lowercase();
+        let invalid = || StandingOrderError::InvalidInterval(value.clone());
+
+        match value.as_str() {
+            "@hourly" => return Ok(Interval::Every(Duration::hours(1))),
+            "@daily" => return Ok(Interval::Every(Duration::days(1))),
+            "@weekly" => return Ok(Interval::Every(Duration::weeks(1))),
+            "@monthly" => return Ok(Interval::Months(1)),
+            "@yearly" | "@annually" => return Ok(Interval::Months(12)),
+            _ => {}
+        }
+
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (count, unit) = value.split_at(split);
+        let count: u32 = count.parse().map_err(|_| invalid())?;
+        if count == 0 {
+            return Err(invalid());
+        }
+
+        let count = i64::from(count);
+        let interval = match unit {
+            "m" | "min" => Interval::Every(Duration::minutes(count)),
+            "h" => Interval::Every(Duration::hours(count)),
+            "d" => Interval::Every(Duration::days(count)),
+            "w" => Interval::Every(Duration::weeks(count)),
+            "mo" => u32::try_from(count)
+                .map(Interval::Months)
+                .map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+
+        let too_long = match interval {
+            Interval::Every(duration) => {
+                duration > Duration::days(366 * i64::from(MAX_INTERVAL_YEARS))
+            }
+            Interval::Months(months) => months > MAX_INTERVAL_YEARS * 12,
+        };
+        if too_long {
+            return Err(invalid());
+        }
+
+        Ok(interval)
+    }
+
+    /// The shortest time between two payments at this interval.
+    pub fn shortest(&self) -> Duration {
+        match self {
+            Interval::Every(duration) => *duration,
+            // February is the shortest month
+            Interval::Months(months) => Duration::days(28) * *months as i32,
+        }
+    }
+
+    /// The occurrence one interval after `time`.
+    pub fn after(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Interval::Every(duration) => time
+                .checked_add_signed(*duration)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            Interval::Months(months) => time
+                .checked_add_months(Months::new(*months))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        }
+    }
+
+    /// The first occurrence on the schedule starting at `start` that is later than `now`.
+    /// Occurrences missed in between are skipped rather than paid all at once. Occurrences are
+    /// counted from `start`, so a schedule starting on the 31st pays on the last day of shorter
+    /// months without moving to an earlier day for good.
+    pub fn next_after(&self, start: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+        if start > now {
+            return start;
+        }
+
+        match self {
+            Interval::Every(duration) => {
+                let missed = (now - start).num_seconds() / duration.num_seconds().max(1);
+                i32::try_from(missed + 1)
+                    .ok()
+                    .and_then(|count| duration.checked_mul(count))
+                    .and_then(|offset| start.checked_add_signed(offset))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC)
+            }
+            Interval::Months(months) => {
+                let occurrence = |count: u32| {
+                    count
+                        .checked_mul(*months)
+                        .and_then(|total| start.checked_add_months(Months::new(total)))
+                        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+                };
+
+                let mut count = 1;
+                let mut next = occurrence(count);
+                while next <= now {
+                    count += 1;
+                    next = occurrence(count);
+                }
+                next
+            }
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Try the payment again later, skipping it once the retries run out.
+    #[default]
+    Retry,
+    /// Skip the payment and wait for the next one.
+    Skip,
+    /// Cancel the order.
+    Cancel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StandingOrderStatus {
+    Active,
+    Paused,
+    Cancelled,
+    Completed,
+}
+
+/// A recurring transfer from one wallet to another, paid by the scheduler until its end date.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
+    pub from: Thing,
+    #[serde(serialize_with = "serialize_table")]
+    pub to: Thing,
+    pub amount: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    /// The member who created the order to pay from a wallet that isn't their own. Payments are
+    /// only made while they are still allowed to make them.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub created_by: Option<Thing>,
+    pub interval: String,
+    /// When the first payment was scheduled, later payments are counted from it.
+    pub anchor: Datetime,
+    pub on_failure: FailurePolicy,
+    pub status: StandingOrderStatus,
+    /// When the next payment is scheduled.
+    pub next_run_at: Datetime,
+    /// When the scheduled payment is attempted again after it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_at: Option<Datetime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_at: Option<Datetime>,
+    /// Payments made so far.
+    pub runs: i64,
+    /// Failed attempts at the scheduled payment.
+    pub failures: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<Datetime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// The transfers made by the order.
+    #[serde(default, serialize_with = "serialize_table_vec")]
+    pub transactions: Vec<Thing>,
+    pub created_at: Datetime,
+}
+
+/// The outcome of an attempt at a payment, recorded on the order.
+struct RunOutcome {
+    status: StandingOrderStatus,
+    next_run_at: DateTime<Utc>,
+    retry_at: Option<DateTime<Utc>>,
+    failures: i64,
+    error: Option<String>,
+    transaction: Option<Thing>,
+}
+
+impl Model {
+    /// Get a standing order from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let thing = Thing::from(("standing_order", Id::from(id.as_ref())));
+        let q = "SELECT * FROM standing_order WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the standing orders paying from or to a wallet, newest first.
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = "SELECT * FROM standing_order WHERE from = $wallet OR to = $wallet ORDER BY created_at DESC LIMIT $limit START $offset;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Get active orders with a payment due, oldest first.
+    pub async fn due(db: &Surreal<Any>, limit: u64) -> Result<Vec<Model>, surrealdb::Error> {
+        let q = r#"
+            SELECT * FROM standing_order
+            WHERE status = 'active' AND (retry_at ?? next_run_at) <= time::now()
+            ORDER BY next_run_at ASC LIMIT $limit;
+        "#;
+
+        let mut response = db.query(q).bind(("limit", limit)).await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Create a standing order paying `amount` every `interval`, starting at `start_at` or one
+    /// interval from now, until `end_at` if given.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        db: &Surreal<Any>,
+        from: &wallet::Sender,
+        to: &wallet::Model,
+        amount: Decimal,
+        interval: &str,
+        start_at: Option<DateTime<Utc>>,
+        end_at: Option<DateTime<Utc>>,
+        on_failure: FailurePolicy,
+        metadata: Option<String>,
+    ) -> Result<Model, KromerError> {
+        if amount <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+        if from.wallet.id == to.id {
+            return Err(KromerError::Validation(
+                "A standing order cannot pay the wallet it is paid from".into(),
+            ));
+        }
+
+        let parsed = Interval::parse(interval)?;
+        if parsed.shortest() < *STANDING_ORDER_MIN_INTERVAL {
+            return Err(KromerError::StandingOrder(
+                StandingOrderError::InvalidInterval(format!(
+                    "{interval} is shorter than {} minutes",
+                    STANDING_ORDER_MIN_INTERVAL.num_minutes()
+                )),
+            ));
+        }
+
+        let now = Utc::now();
+        let next_run_at = match start_at {
+            Some(start_at) if start_at < now => {
+                return Err(KromerError::Validation("Start date is in the past".into()))
+            }
+            Some(start_at) => start_at,
+            None => parsed.after(now),
+        };
+        if end_at.is_some_and(|end_at| end_at < next_run_at) {
+            return Err(KromerError::Validation(
+                "End date is before the first payment".into(),
+            ));
+        }
+
+        from.wallet.ensure_can_send()?;
+        to.ensure_can_receive()?;
+
+        let q = r#"
+            CREATE ONLY standing_order CONTENT {
+                from: $from,
+                to: $to,
+                amount: $amount,
+                metadata: $metadata,
+                created_by: $created_by,
+                interval: $interval,
+                anchor: $next_run_at,
+                on_failure: $on_failure,
+                next_run_at: $next_run_at,
+                end_at: $end_at,
+            };
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("from", from.wallet.id.clone()))
+            .bind((
+                "created_by",
+                from.spender.as_ref().map(|spender| spender.player.clone()),
+            ))
+            .bind(("to", to.id.clone()))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .bind(("interval", interval.trim().to_lowercase()))
+            .bind(("on_failure", on_failure))
+            .bind(("next_run_at", Datetime::from(next_run_at)))
+            .bind(("end_at", end_at.map(Datetime::from)))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Internal(
+            "Unable to get created standing order",
+        ))
+    }
+
+    /// Pause an active order. No payments are made until it is resumed.
+    pub async fn pause(db: &Surreal<Any>, order: &Model) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET status = 'paused' WHERE status = 'active' RETURN AFTER)[0];";
+
+        let mut response = db.query(q).bind(("id", order.id.clone())).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::StandingOrder(StandingOrderError::NotActive))
+    }
+
+    /// Resume a paused order. Payments that were due while it was paused are skipped.
+    pub async fn resume(db: &Surreal<Any>, order: &Model) -> Result<Model, KromerError> {
+        let interval = Interval::parse(&order.interval)?;
+        let next_run_at = interval.next_after(*order.anchor, Utc::now());
+        let completed = order.end_at.as_ref().is_some_and(|end| next_run_at > **end);
+
+        let q = r#"
+            (UPDATE $id SET
+                status = IF $completed { 'completed' } ELSE { 'active' },
+                next_run_at = $next_run_at,
+                retry_at = NONE,
+                failures = 0
+            WHERE status = 'paused'
+            RETURN AFTER)[0];
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", order.id.clone()))
+            .bind(("completed", completed))
+            .bind(("next_run_at", Datetime::from(next_run_at)))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::StandingOrder(StandingOrderError::NotPaused))
+    }
+
+    /// Cancel an order that is active or paused.
+    pub async fn cancel(db: &Surreal<Any>, order: &Model) -> Result<Model, KromerError> {
+        let q = "(UPDATE $id SET status = 'cancelled' WHERE status IN ['active', 'paused'] RETURN AFTER)[0];";
+
+        let mut response = db.query(q).bind(("id", order.id.clone())).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::StandingOrder(StandingOrderError::Finished))
+    }
+
+    /// Make the payment that is due through the normal transfer path and schedule the next one.
+    /// A failed payment is handled according to the failure policy of the order.
+    /// Returns the updated order and the transfer if the payment succeeded.
+    pub async fn execute(
+        &self,
+        db: &Surreal<Any>,
+    ) -> Result<(Model, Option<transaction::Model>), surrealdb::Error> {
+        let now = Utc::now();
+        let interval = match Interval::parse(&self.interval) {
+            Ok(interval) => interval,
+            Err(e) => {
+                let outcome = RunOutcome {
+                    status: StandingOrderStatus::Cancelled,
+                    next_run_at: *self.next_run_at,
+                    retry_at: None,
+                    failures: self.failures,
+                    error: Some(e.to_string()),
+                    transaction: None,
+                };
+                return Ok((self.record(db, outcome).await?, None));
+            }
+        };
+
+        let next_run_at = interval.next_after(*self.anchor, now);
+        let status = match &self.end_at {
+            Some(end) if next_run_at > **end => StandingOrderStatus::Completed,
+            _ => StandingOrderStatus::Active,
+        };
+
+        let outcome = match self.transfer(db).await {
+            Ok(transaction) => {
+                let outcome = RunOutcome {
+                    status,
+                    next_run_at,
+                    retry_at: None,
+                    failures: 0,
+                    error: None,
+                    transaction: transaction.id.clone(),
+                };
+                return Ok((self.record(db, outcome).await?, Some(transaction)));
+            }
+            // The database being unavailable is not the fault of the order, so it is tried again
+            // on the next round without counting as a failure.
+            Err(KromerError::Database(e)) => return Err(e),
+            Err(e) => {
+                let failures = self.failures + 1;
+                let error = Some(e.to_string());
+
+                match self.on_failure {
+                    FailurePolicy::Retry if failures < *STANDING_ORDER_MAX_RETRIES => RunOutcome {
+                        status: StandingOrderStatus::Active,
+                        next_run_at: *self.next_run_at,
+                        retry_at: Some(now + *STANDING_ORDER_RETRY_DELAY),
+                        failures,
+                        error,
+                        transaction: None,
+                    },
+                    FailurePolicy::Retry | FailurePolicy::Skip => RunOutcome {
+                        status,
+                        next_run_at,
+                        retry_at: None,
+                        failures: 0,
+                        error,
+                        transaction: None,
+                    },
+                    FailurePolicy::Cancel => RunOutcome {
+                        status: StandingOrderStatus::Cancelled,
+                        next_run_at: *self.next_run_at,
+                        retry_at: None,
+                        failures,
+                        error,
+                        transaction: None,
+                    },
+                }
+            }
+        };
+
+        Ok((self.record(db, outcome).await?, None))
+    }
+
+    /// Make the payment, authorized again like a new transfer, in case the member who created
+    /// the order lost their role or the wallet got a multisig policy in the meantime.
+    async fn transfer(&self, db: &Surreal<Any>) -> Result<transaction::Model, KromerError> {
+        let from = wallet::Model::get(db, self.from.to_raw())
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+        let to = wallet::Model::get(db, self.to.to_raw())
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+        let memberships = match &self.created_by {
+            Some(player) => {
+                let members = wallet::Model::get_members(db, self.from.clone()).await?;
+                Some(
+                    members
+                        .into_iter()
+                        .filter(|member| member.player == *player)
+                        .collect(),
+                )
+            }
+            None => None,
+        };
+        let from =
+            wallet::Model::authorize_spend(db, from, memberships, Some(&to), self.amount).await?;
+
+        transaction::Model::transfer(
+            db,
+            &from.wallet,
+            from.spender.as_ref(),
+            &to,
+            self.amount,
+            self.metadata.clone(),
+        )
+        .await
+    }
+
+    /// Record the outcome of an attempt. An order paused or cancelled while the payment was being
+    /// made keeps its new status.
+    async fn record(
+        &self,
+        db: &Surreal<Any>,
+        outcome: RunOutcome,
+    ) -> Result<Model, surrealdb::Error> {
+        let q = r#"
+            UPDATE ONLY $id SET
+                status = IF status = 'active' { $status } ELSE { status },
+                next_run_at = $next_run_at,
+                retry_at = $retry_at,
+                failures = $failures,
+                last_run_at = time::now(),
+                last_error = $error,
+                runs += IF $transaction { 1 } ELSE { 0 },
+                transactions += IF $transaction { [$transaction] } ELSE { [] }
+            RETURN AFTER;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("id", self.id.clone()))
+            .bind(("status", outcome.status))
+            .bind(("next_run_at", Datetime::from(outcome.next_run_at)))
+            .bind(("retry_at", outcome.retry_at.map(Datetime::from)))
+            .bind(("failures", outcome.failures))
+            .bind(("error", outcome.error))
+            .bind(("transaction", outcome.transaction))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model.unwrap_or_else(|| self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(
+            Interval::parse("30m").unwrap(),
+            Interval::Every(Duration::minutes(30))
+        );
+        assert_eq!(
+            Interval::parse(" 2W ").unwrap(),
+            Interval::Every(Duration::weeks(2))
+        );
+        assert_eq!(
+            Interval::parse("@daily").unwrap(),
+            Interval::Every(Duration::days(1))
+        );
+        assert_eq!(Interval::parse("3mo").unwrap(), Interval::Months(3));
+        assert_eq!(Interval::parse("@yearly").unwrap(), Interval::Months(12));
+
+        for invalid in [
+            "",
+            "d",
+            "0d",
+            "5",
+            "1y",
+            "-1h",
+            "@never",
+            "100000000d",
+            "4000000000w",
+            "121mo",
+        ] {
+            assert!(
+                Interval::parse(invalid).is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_after_skips_missed() {
+        let start = DateTime::parse_from_rfc3339("2024-01-31T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let now = start + Duration::hours(50);
+
+        let daily = Interval::Every(Duration::days(1));
+        assert_eq!(daily.next_after(start, now), start + Duration::days(3));
+        assert_eq!(daily.next_after(now, start), now);
+
+        let monthly = Interval::Months(1);
+        assert_eq!(
+            monthly.next_after(start, now).to_rfc3339(),
+            "2024-02-29T12:00:00+00:00"
+        );
+        assert_eq!(
+            monthly
+                .next_after(start, start + Duration::days(31))
+                .to_rfc3339(),
+            "2024-03-31T12:00:00+00:00"
+        );
+    }
+}
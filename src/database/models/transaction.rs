@@ -1,19 +1,39 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use surrealdb::{
     engine::any::Any,
+    method::Query,
     sql::{Datetime, Id, Thing},
     Surreal,
 };
 
 use rust_decimal::Decimal;
 
-use super::{serialize_table_opt, CountResponse};
-use crate::{models::transactions::TransactionType, routes::PaginationParams};
+use super::{name, serialize_table_opt, wallet, CountResponse};
+use crate::database::cursor::{order_clause, Cursor, CursorPage, CursorPosition, Cursored};
+use crate::fees::FEE_SCHEDULE;
+use crate::{
+    errors::{name::NameError, transaction::TransactionError, wallet::WalletError, KromerError},
+    models::{deserialize_comma_separated, transactions::TransactionType},
+    routes::PaginationParams,
+};
+
+/// Creates the system mint wallet if it doesn't exist yet. Expects `$mint_address` to be bound.
+pub(super) const ENSURE_MINT_WALLET: &str = r#"
+            IF !record::exists(wallet:mint) {
+                CREATE wallet:mint CONTENT { address: $mint_address, hash: crypto::argon2::generate(rand::string(32)) };
+            };"#;
 
 static KST_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([a-z0-9]{1,64})\.kst").unwrap());
 
+/// A whole CommonMeta record naming a name target, e.g. `meta@name.kst`.
+static NAME_TARGET_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:([a-z0-9-_]{1,32})@)?([a-z0-9]{1,64})\.kst$").unwrap());
+
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Model {
     #[serde(
@@ -28,6 +48,34 @@ pub struct Model {
     pub timestamp: Datetime,
     pub to: Thing,
     pub transaction_type: TransactionType,
+    /// The name this transaction was about, for name purchases and transfers.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub name: Option<Thing>,
+    /// The transaction this one refunds.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub refund_of: Option<Thing>,
+    /// The wallet that was credited, which for payments to a name is the owner of the name at the time.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub recipient: Option<Thing>,
+    /// The transfer this fee was charged for.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub fee_of: Option<Thing>,
 }
 
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -37,6 +85,134 @@ pub struct TransactionCreateData {
     pub amount: Decimal,
     pub metadata: Option<String>,
     pub transaction_type: TransactionType,
+    pub name: Option<Thing>,
+}
+
+/// Where the funds of a batch of payouts come from.
+#[derive(Clone, Copy, Debug)]
+pub enum BatchSource<'a> {
+    /// New money, recorded as `mint` transactions.
+    Mint,
+    /// A funding wallet, recorded as `transfer` transactions.
+    Wallet(&'a wallet::Model),
+}
+
+/// A single payout of a batch.
+#[derive(Clone, Debug)]
+pub struct BatchPayout {
+    pub to: wallet::Model,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+}
+
+/// Typed filters for querying transactions. All given filters have to match for a transaction to be returned.
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct TransactionFilter {
+    /// Address of the sender.
+    pub from: Option<String>,
+    /// Address of the recipient. Payments to names are matched by the wallet that was credited.
+    pub to: Option<String>,
+    /// Comma separated addresses, matching transactions where any of them is either the sender or the recipient.
+    #[serde(
+        default,
+        rename = "address",
+        deserialize_with = "deserialize_comma_separated"
+    )]
+    pub addresses: Vec<String>,
+    #[serde(rename = "type")]
+    pub transaction_type: Option<TransactionType>,
+    pub min_amount: Option<Decimal>,
+    pub max_amount: Option<Decimal>,
+    /// Only include transactions made at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only include transactions made at or before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// Case insensitive text the metadata has to contain.
+    pub metadata: Option<String>,
+}
+
+/// Field to sort transactions by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionSort {
+    #[default]
+    Time,
+    Amount,
+    /// Address of the sender.
+    From,
+    /// Address of the wallet that received the transaction, the owner of the name for payments to names.
+    To,
+}
+
+impl TransactionSort {
+    fn field(&self) -> &'static str {
+        match self {
+            TransactionSort::Time => "timestamp",
+            TransactionSort::Amount => "amount",
+            TransactionSort::From => "from.address",
+            TransactionSort::To => "recipient.address",
+        }
+    }
+}
+
+/// Order to return transactions in. Defaults to newest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionOrder {
+    pub sort: TransactionSort,
+    pub ascending: bool,
+}
+
+impl Default for TransactionOrder {
+    fn default() -> Self {
+        Self {
+            sort: TransactionSort::Time,
+            ascending: false,
+        }
+    }
+}
+
+impl TransactionOrder {
+    /// Record fields can't be ordered by directly, so the sort field is selected as `sort_key`.
+    /// Ties are broken by time, newest first.
+    fn clause(&self) -> String {
+        let direction = if self.ascending { "ASC" } else { "DESC" };
+
+        format!("ORDER BY sort_key {direction}, timestamp DESC")
+    }
+}
+
+/// Aggregated transaction activity over a period of time.
+#[derive(Debug, Default, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct TransactionActivity {
+    pub transactions: usize,
+    pub volume: Decimal,
+    pub active_wallets: usize,
+}
+
+/// A wallet and the total amount it sent and received over a period of time.
+#[derive(Debug, Default, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct TopMover {
+    pub address: String,
+    pub volume: Decimal,
+}
+
+/// Metadata parsed according to CommonMeta: `;` separated records that are either `key=value`
+/// pairs or standalone values, where a leading `meta@name.kst` record is the name it was sent to.
+#[derive(Debug, Default, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct CommonMeta {
+    /// The name target the transaction was sent to, e.g. `meta@name.kst`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metaname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Where refunds should be sent, an address or a name target, from the `return` key.
+    #[serde(rename = "return", skip_serializing_if = "Option::is_none")]
+    pub return_target: Option<String>,
+    /// All `key=value` pairs. If a key is given more than once, the last value wins.
+    pub entries: BTreeMap<String, String>,
+    /// Records without a key, other than the name target.
+    pub values: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -131,6 +307,679 @@ impl Model {
 
         Ok(models)
     }
+
+    /// Get transactions matching a filter in the given order, omitting id.
+    pub async fn filtered(
+        db: &Surreal<Any>,
+        filter: &TransactionFilter,
+        order: &TransactionOrder,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = format!(
+            "{} SELECT *, {} AS sort_key OMIT id FROM transaction {} {} LIMIT $limit START $offset;",
+            TransactionFilter::PRELUDE,
+            order.sort.field(),
+            filter.where_clause(),
+            order.clause()
+        );
+
+        let query = db.query(q).bind(("limit", limit)).bind(("offset", offset));
+        let mut response = filter.bind(query).await?;
+        let models: Vec<Model> = response.take(TransactionFilter::PRELUDE_STATEMENTS)?;
+
+        Ok(models)
+    }
+
+    /// Get a page of transactions matching a filter, newest first.
+    pub async fn page(
+        db: &Surreal<Any>,
+        filter: &TransactionFilter,
+        limit: u64,
+        position: Option<&CursorPosition>,
+    ) -> Result<CursorPage<Model>, surrealdb::Error> {
+        let mut conditions: Vec<String> =
+            filter.conditions().into_iter().map(String::from).collect();
+        conditions.extend(position.map(|position| position.condition("timestamp")));
+
+        let q = format!(
+            "{} SELECT * FROM transaction {} {} LIMIT $limit;",
+            TransactionFilter::PRELUDE,
+            where_clause(&conditions),
+            order_clause("timestamp", position)
+        );
+
+        let cursor = position.map(|position| position.cursor.clone());
+        let query = db
+            .query(q)
+            .bind(("limit", limit + 1))
+            .bind(("cursor_time", cursor.as_ref().map(|c| c.time.clone())))
+            .bind(("cursor_id", cursor.map(|c| c.id)));
+        let mut response = filter.bind(query).await?;
+        let models: Vec<Model> = response.take(TransactionFilter::PRELUDE_STATEMENTS)?;
+
+        Ok(CursorPage::new(models, limit as usize, position))
+    }
+
+    /// Get the amount of transactions matching a filter
+    pub async fn count_filtered(
+        db: &Surreal<Any>,
+        filter: &TransactionFilter,
+    ) -> Result<usize, surrealdb::Error> {
+        let q = format!(
+            "{} (SELECT count() FROM transaction {} GROUP BY count)[0] or {{ count: 0 }};",
+            TransactionFilter::PRELUDE,
+            filter.where_clause()
+        );
+
+        let mut response = filter.bind(db.query(q)).await?;
+        let count: Option<CountResponse> = response.take(TransactionFilter::PRELUDE_STATEMENTS)?;
+        let count = count.unwrap_or_default();
+
+        Ok(count.count)
+    }
+
+    /// Transfer money from one wallet to another, recording a `transfer` transaction.
+    /// Transfers by a `spender` are recorded as theirs, and their spending limit is checked again inside the database
+    /// transaction, like the available balance.
+    pub async fn transfer(
+        db: &Surreal<Any>,
+        from: &wallet::Model,
+        spender: Option<&wallet::Spender>,
+        to: &wallet::Model,
+        amount: Decimal,
+        metadata: Option<String>,
+    ) -> Result<Model, KromerError> {
+        // Check on the server so DB doesnt throw.
+        if amount < Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+
+        from.ensure_can_send()?;
+        to.ensure_can_receive()?;
+
+        let fee = FEE_SCHEDULE.quote(db, from, to, amount).await?;
+        let fee_amount = fee.as_ref().map_or(Decimal::ZERO, |fee| fee.amount);
+
+        // Make sure to check the request to see if the funds are available.
+        if from.available_balance() < amount + fee_amount {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        let from_id = from
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+        let to_id = to
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+        let treasury = fee.map(|fee| fee.treasury.id);
+
+        // The fee is charged in the same database transaction, linked to the transfer it was charged for.
+        let q = r#"
+            BEGIN TRANSACTION;
+            IF $from.available < $amount + $fee { THROW "Insufficient funds" };
+            IF $spending_limit != NONE {
+                LET $spent = math::sum(SELECT VALUE amount FROM transaction WHERE from = $from AND spent_by = $spender AND timestamp > $since) ?? 0dec;
+                IF $spent + $amount + $fee > $spending_limit { THROW "Spending limit exceeded" };
+            };
+            LET $created = CREATE ONLY transaction CONTENT { from: $from, to: $to, amount: $amount, metadata: $metadata, transaction_type: 'transfer', spent_by: $spender };
+            IF $treasury {
+                CREATE transaction CONTENT { from: $from, to: $treasury, amount: $fee, metadata: 'type=fee;ref=' + <string> $created.id, transaction_type: 'fee', fee_of: $created.id, spent_by: $spender };
+            };
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("from", from_id))
+            .bind(("to", to_id))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .bind(("fee", fee_amount))
+            .bind(("treasury", treasury))
+            .bind(("spender", spender.map(|spender| spender.player.clone())))
+            .bind((
+                "spending_limit",
+                spender.and_then(|spender| spender.spending_limit),
+            ))
+            .bind(("since", wallet::Spender::window_start()))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Transaction(TransactionError::FailedCreate))
+    }
+
+    /// Mint new money into a wallet, recording a `mint` transaction from the system mint wallet.
+    /// The mint wallet is created on first use, its password is random and never returned.
+    pub async fn mint(
+        db: &Surreal<Any>,
+        to: &wallet::Model,
+        amount: Decimal,
+        metadata: Option<String>,
+    ) -> Result<Model, KromerError> {
+        let to_id = to
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+        let q = format!(
+            r#"
+            BEGIN TRANSACTION;
+            {ENSURE_MINT_WALLET}
+            LET $created = CREATE ONLY transaction CONTENT {{ from: wallet:mint, to: $to, amount: $amount, metadata: $metadata, transaction_type: 'mint' }};
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#
+        );
+
+        let mut response = db
+            .query(q)
+            .bind(("mint_address", wallet::MINT_ADDRESS))
+            .bind(("to", to_id))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Internal("Unable to get mint transaction"))
+    }
+
+    /// Remove money from a wallet, recording a `burn` transaction to the system mint wallet.
+    /// Funds reserved by holds are never taken. If `force` is set and the wallet can't cover the full amount, its whole available balance is taken instead.
+    pub async fn burn(
+        db: &Surreal<Any>,
+        from: &wallet::Model,
+        amount: Decimal,
+        force: bool,
+        metadata: Option<String>,
+    ) -> Result<Model, KromerError> {
+        let from_id = from
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+        let available = from.available_balance();
+        if available <= Decimal::ZERO || (!force && available < amount) {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+
+        // The balance is checked again inside the transaction in case it changed in the meantime.
+        let q = format!(
+            r#"
+            BEGIN TRANSACTION;
+            {ENSURE_MINT_WALLET}
+            LET $balance = $from.available;
+            LET $taken = IF $force {{ math::min([$amount, $balance]) }} ELSE {{ $amount }};
+            IF $taken <= 0 OR $balance < $taken {{ THROW "Insufficient funds" }};
+            LET $created = CREATE ONLY transaction CONTENT {{ from: $from, to: wallet:mint, amount: $taken, metadata: $metadata, transaction_type: 'burn' }};
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#
+        );
+
+        let mut response = db
+            .query(q)
+            .bind(("mint_address", wallet::MINT_ADDRESS))
+            .bind(("from", from_id))
+            .bind(("amount", amount))
+            .bind(("force", force))
+            .bind(("metadata", metadata))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Internal("Unable to get burn transaction"))
+    }
+
+    /// How much the transaction changed the balance of a wallet, following the `transfer_balance`
+    /// database event: name purchases and burns only debit the sender, mints only credit the
+    /// recipient, everything else moves the amount from the sender to the recipient.
+    pub fn balance_change(&self, wallet: &Thing) -> Decimal {
+        let recipient = self.recipient.as_ref().unwrap_or(&self.to);
+        let (debits, credits) = match self.transaction_type {
+            TransactionType::NamePurchase | TransactionType::Burn => (true, false),
+            TransactionType::Mint => (false, true),
+            _ => (true, true),
+        };
+
+        let mut change = Decimal::ZERO;
+        if debits && &self.from == wallet {
+            change -= self.amount;
+        }
+        if credits && recipient == wallet {
+            change += self.amount;
+        }
+
+        change
+    }
+
+    /// Make a batch of payouts in a single database transaction, so either all of them are made or
+    /// none are. Returns the created transactions in the order of the payouts.
+    pub async fn batch(
+        db: &Surreal<Any>,
+        source: BatchSource<'_>,
+        payouts: Vec<BatchPayout>,
+    ) -> Result<Vec<Model>, KromerError> {
+        if payouts.is_empty() {
+            return Err(KromerError::Validation("Batch has no payouts".into()));
+        }
+        for payout in &payouts {
+            if payout.amount <= Decimal::ZERO {
+                return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+            }
+            payout.to.ensure_can_receive()?;
+        }
+
+        let total: Decimal = payouts.iter().map(|payout| payout.amount).sum();
+        let (from, transaction_type, check) = match source {
+            BatchSource::Mint => (
+                Thing::from(("wallet", "mint")),
+                TransactionType::Mint,
+                ENSURE_MINT_WALLET,
+            ),
+            BatchSource::Wallet(from) => {
+                from.ensure_can_send()?;
+                if from.available_balance() < total {
+                    return Err(KromerError::Transaction(
+                        TransactionError::InsufficientFunds,
+                    ));
+                }
+
+                let from = from
+                    .id
+                    .clone()
+                    .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+                // The available balance is checked again inside the transaction in case it changed in the meantime.
+                (
+                    from,
+                    TransactionType::Transfer,
+                    r#"IF $from.available < $total { THROW "Insufficient funds" };"#,
+                )
+            }
+        };
+
+        let mut lines = Vec::with_capacity(payouts.len());
+        for payout in payouts {
+            lines.push(TransactionCreateData {
+                from: from.clone(),
+                to: payout
+                    .to
+                    .id
+                    .ok_or(KromerError::Internal("Wallet is missing its id"))?,
+                amount: payout.amount,
+                metadata: payout.metadata,
+                transaction_type: transaction_type.clone(),
+                name: None,
+            });
+        }
+        let count = lines.len();
+
+        let q = format!(
+            r#"
+            BEGIN TRANSACTION;
+            {check}
+            LET $created = INSERT INTO transaction $lines;
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#
+        );
+
+        let mut response = db
+            .query(q)
+            .bind(("mint_address", wallet::MINT_ADDRESS))
+            .bind(("from", from))
+            .bind(("total", total))
+            .bind(("lines", lines))
+            .await?;
+        let index = response.num_statements() - 1;
+        let models: Vec<Model> = response.take(index)?;
+
+        if models.len() != count {
+            return Err(KromerError::Transaction(TransactionError::FailedCreate));
+        }
+
+        Ok(models)
+    }
+
+    /// Refund all or part of a transaction, linking the refund to it.
+    /// All refunds of a transaction together can't exceed its amount.
+    pub async fn refund(
+        db: &Surreal<Any>,
+        refunded: &Model,
+        from: &wallet::Model,
+        to: &wallet::Model,
+        amount: Decimal,
+        metadata: String,
+    ) -> Result<Model, KromerError> {
+        if amount <= Decimal::ZERO {
+            return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+        }
+
+        from.ensure_can_send()?;
+        to.ensure_can_receive()?;
+
+        if from.available_balance() < amount {
+            return Err(KromerError::Transaction(
+                TransactionError::InsufficientFunds,
+            ));
+        }
+        if Self::refunded_amount(db, refunded).await? + amount > refunded.amount {
+            return Err(KromerError::Transaction(
+                TransactionError::RefundExceedsAmount,
+            ));
+        }
+
+        // The available balance and refunded amount are checked again inside the transaction in case of concurrent transfers or refunds.
+        let q = r#"
+            BEGIN TRANSACTION;
+            IF $from.available < $amount { THROW "Insufficient funds" };
+            LET $refunded_amount = math::sum(SELECT VALUE amount FROM transaction WHERE refund_of = $refunded) ?? 0dec;
+            IF $refunded_amount + $amount > $refunded.amount { THROW "Refund exceeds the transaction amount" };
+            LET $created = CREATE ONLY transaction CONTENT { from: $from, to: $to, amount: $amount, metadata: $metadata, transaction_type: 'transfer', refund_of: $refunded };
+            RETURN $created;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("refunded", refunded.id.clone()))
+            .bind(("from", from.id.clone()))
+            .bind(("to", to.id.clone()))
+            .bind(("amount", amount))
+            .bind(("metadata", metadata))
+            .await?;
+        let index = response.num_statements() - 1;
+        let model: Option<Model> = response.take(index)?;
+
+        model.ok_or(KromerError::Transaction(TransactionError::FailedCreate))
+    }
+
+    /// Get the wallet that received a transaction. For payments to a name, this is the wallet that
+    /// owned it at the time if it was recorded, otherwise its current or last owner.
+    pub async fn recipient_wallet(&self, db: &Surreal<Any>) -> Result<wallet::Model, KromerError> {
+        let recipient = match (&self.recipient, self.to.tb.as_str()) {
+            (Some(recipient), _) => recipient.clone(),
+            (None, "name") => name::Model::owner_of(db, &self.to.id.to_raw())
+                .await?
+                .ok_or(KromerError::Name(NameError::NotFound))?,
+            (None, _) => self.to.clone(),
+        };
+        let wallet = wallet::Model::get(db, recipient.to_raw()).await?;
+
+        wallet.ok_or(KromerError::Wallet(WalletError::NotFound))
+    }
+
+    /// Get where a refund of the transaction should go: the CommonMeta `return` target if given,
+    /// otherwise the sender. Also returns the name target to put in the refund metadata, if any.
+    /// A name that was released since is resolved to the wallet that owned it last.
+    pub async fn refund_target(
+        &self,
+        db: &Surreal<Any>,
+    ) -> Result<(wallet::Model, Option<String>), KromerError> {
+        let meta = CommonMeta::parse(self.metadata.as_deref().unwrap_or_default());
+
+        let Some(target) = meta.return_target else {
+            let sender = wallet::Model::get(db, self.from.to_raw())
+                .await?
+                .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+            return Ok((sender, None));
+        };
+
+        match CommonMeta::target_name(&target) {
+            Some(name) => {
+                let owner = name::Model::owner_of(db, name)
+                    .await?
+                    .ok_or(KromerError::Name(NameError::NotFound))?;
+                let owner = wallet::Model::get(db, owner.to_raw())
+                    .await?
+                    .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+                Ok((owner, Some(target)))
+            }
+            None => {
+                let wallet = wallet::Model::get_by_address(db, target)
+                    .await?
+                    .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+                Ok((wallet, None))
+            }
+        }
+    }
+
+    /// Get how much of a transaction was refunded so far.
+    pub async fn refunded_amount(
+        db: &Surreal<Any>,
+        refunded: &Model,
+    ) -> Result<Decimal, surrealdb::Error> {
+        let q = "RETURN math::sum(SELECT VALUE amount FROM transaction WHERE refund_of = $refunded) ?? 0dec;";
+
+        let mut response = db.query(q).bind(("refunded", refunded.id.clone())).await?;
+        let amount: Option<Decimal> = response.take(0)?;
+
+        Ok(amount.unwrap_or_default())
+    }
+
+    /// Get the amount of transactions, their volume and the amount of distinct wallets involved since the given time.
+    /// Payments to names are counted towards the wallet that was credited.
+    pub async fn activity_since(
+        db: &Surreal<Any>,
+        since: Datetime,
+    ) -> Result<TransactionActivity, surrealdb::Error> {
+        let q = r#"
+            LET $transactions = SELECT amount, from, recipient FROM transaction WHERE timestamp > $since;
+            RETURN {
+                transactions: array::len($transactions),
+                volume: math::sum($transactions.amount) ?? 0dec,
+                active_wallets: array::len(array::union($transactions.from, $transactions.recipient)),
+            };
+        "#;
+
+        let mut response = db.query(q).bind(("since", since)).await?;
+        let activity: Option<TransactionActivity> = response.take(1)?;
+
+        Ok(activity.unwrap_or_default())
+    }
+
+    /// Get the wallets that moved the most money (sent and received) since the given time, in descending order.
+    pub async fn top_movers_since(
+        db: &Surreal<Any>,
+        since: Datetime,
+        limit: u64,
+    ) -> Result<Vec<TopMover>, surrealdb::Error> {
+        let q = r#"
+            LET $transactions = SELECT amount, from, recipient FROM transaction WHERE timestamp > $since;
+            LET $moves = array::concat(
+                (SELECT from AS wallet, amount FROM $transactions),
+                (SELECT recipient AS wallet, amount FROM $transactions)
+            );
+            RETURN SELECT wallet.address AS address, math::sum(amount) AS volume FROM $moves GROUP BY address ORDER BY volume DESC LIMIT $limit;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("since", since))
+            .bind(("limit", limit))
+            .await?;
+        let models: Vec<TopMover> = response.take(2)?;
+
+        Ok(models)
+    }
+}
+
+impl TransactionFilter {
+    /// Resolves the filtered addresses to wallets once, so the conditions can compare record IDs.
+    const PRELUDE: &'static str = r#"
+        LET $from_wallet = (SELECT VALUE id FROM wallet WHERE address = $from)[0];
+        LET $to_wallet = (SELECT VALUE id FROM wallet WHERE address = $to)[0];
+        LET $wallets = SELECT VALUE id FROM wallet WHERE address IN $addresses;
+    "#;
+    const PRELUDE_STATEMENTS: usize = 3;
+
+    /// Build the `WHERE` clause for the filters that are set, or an empty string if there are none.
+    pub fn where_clause(&self) -> String {
+        where_clause(&self.conditions())
+    }
+
+    /// Get the conditions for the filters that are set.
+    fn conditions(&self) -> Vec<&'static str> {
+        let mut conditions = Vec::new();
+
+        if self.from.is_some() {
+            conditions.push("from = $from_wallet");
+        }
+        if self.to.is_some() {
+            conditions.push("recipient = $to_wallet");
+        }
+        if !self.addresses.is_empty() {
+            conditions.push("(from IN $wallets OR recipient IN $wallets)");
+        }
+        if self.transaction_type.is_some() {
+            conditions.push("transaction_type = $transaction_type");
+        }
+        if self.min_amount.is_some() {
+            conditions.push("amount >= $min_amount");
+        }
+        if self.max_amount.is_some() {
+            conditions.push("amount <= $max_amount");
+        }
+        if self.since.is_some() {
+            conditions.push("timestamp >= $since");
+        }
+        if self.until.is_some() {
+            conditions.push("timestamp <= $until");
+        }
+        if self.metadata.is_some() {
+            conditions.push("string::lowercase(metadata ?? '') CONTAINS $metadata");
+        }
+
+        conditions
+    }
+
+    /// Bind every parameter used by [`TransactionFilter::PRELUDE`] and [`TransactionFilter::where_clause`].
+    fn bind<'r>(&self, query: Query<'r, Any>) -> Query<'r, Any> {
+        query
+            .bind(("from", self.from.clone()))
+            .bind(("to", self.to.clone()))
+            .bind(("addresses", self.addresses.clone()))
+            .bind(("transaction_type", self.transaction_type.clone()))
+            .bind(("min_amount", self.min_amount))
+            .bind(("max_amount", self.max_amount))
+            .bind(("since", self.since.map(Datetime::from)))
+            .bind(("until", self.until.map(Datetime::from)))
+            .bind((
+                "metadata",
+                self.metadata
+                    .as_ref()
+                    .map(|metadata| metadata.to_lowercase()),
+            ))
+    }
+}
+
+impl Cursored for Model {
+    fn cursor(&self) -> Option<Cursor> {
+        Some(Cursor {
+            time: self.timestamp.clone(),
+            id: self.id.clone()?,
+        })
+    }
+}
+
+fn where_clause<S: AsRef<str>>(conditions: &[S]) -> String {
+    if conditions.is_empty() {
+        return String::new();
+    }
+
+    let conditions: Vec<&str> = conditions.iter().map(AsRef::as_ref).collect();
+    format!("WHERE {}", conditions.join(" AND "))
+}
+
+/// Build the metadata of a refund: the name target to send it to if any, a `ref` to the refunded
+/// transaction, `type=refund` and the given notes, e.g. `error=Out of stock`.
+pub fn refund_metadata(target: Option<&str>, refunded: &Thing, notes: &[(&str, &str)]) -> String {
+    let mut records: Vec<String> = target.map(str::to_string).into_iter().collect();
+    records.push(format!("ref={}", refunded.to_raw()));
+    records.push("type=refund".to_string());
+    records.extend(
+        notes
+            .iter()
+            .filter(|(_, value)| !value.trim().is_empty())
+            // `;` separates records, so it can't be part of a value
+            .map(|(key, value)| format!("{key}={}", value.trim().replace(';', ","))),
+    );
+
+    records.join(";")
+}
+
+impl CommonMeta {
+    /// Parse metadata according to CommonMeta.
+    ///
+    /// # Examples
+    /// ```
+    /// use kromer::database::models::transaction::CommonMeta;
+    ///
+    /// let meta = CommonMeta::parse("shop@store.kst;return=kromernya1;thanks");
+    /// assert_eq!(meta.recipient, Some("shop@store.kst".to_string()));
+    /// assert_eq!(meta.return_target, Some("kromernya1".to_string()));
+    /// assert_eq!(meta.values, vec!["thanks".to_string()]);
+    /// ```
+    pub fn parse<S: AsRef<str>>(input: S) -> Self {
+        let mut meta = Self::default();
+
+        let records = input
+            .as_ref()
+            .split(';')
+            .map(str::trim)
+            .filter(|record| !record.is_empty());
+        for (index, record) in records.enumerate() {
+            if let Some((key, value)) = record.split_once('=') {
+                meta.entries
+                    .insert(key.trim().to_string(), value.trim().to_string());
+                continue;
+            }
+
+            match NAME_TARGET_REGEX.captures(record) {
+                Some(captures) if index == 0 => {
+                    meta.recipient = Some(record.to_string());
+                    meta.metaname = captures.get(1).map(|m| m.as_str().to_string());
+                    meta.name = captures.get(2).map(|m| m.as_str().to_string());
+                }
+                _ => meta.values.push(record.to_string()),
+            }
+        }
+        meta.return_target = meta.entries.get("return").cloned();
+
+        meta
+    }
+
+    /// Get the value of a `key=value` pair.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Whether the given target is a name, e.g. `meta@name.kst`, rather than an address.
+    pub fn is_name_target(target: &str) -> bool {
+        NAME_TARGET_REGEX.is_match(target)
+    }
+
+    /// Get the name of a name target, e.g. `name` for `meta@name.kst`.
+    pub fn target_name(target: &str) -> Option<&str> {
+        NAME_TARGET_REGEX
+            .captures(target)
+            .and_then(|captures| captures.get(2))
+            .map(|m| m.as_str())
+    }
 }
 
 impl TransactionNameData {
@@ -211,3 +1060,138 @@ impl TransactionNameData {
         Self::parse(input)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_filter_where_clause() {
+        let filter = TransactionFilter::default();
+        assert_eq!(filter.where_clause(), "");
+    }
+
+    #[test]
+    fn test_filter_where_clause() {
+        let filter = TransactionFilter {
+            from: Some("kromernya1".to_owned()),
+            min_amount: Some(rust_decimal_macros::dec!(1000)),
+            metadata: Some("Shop".to_owned()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            filter.where_clause(),
+            "WHERE from = $from_wallet AND amount >= $min_amount AND string::lowercase(metadata ?? '') CONTAINS $metadata"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_filter_query() {
+        let filter = actix_web::web::Query::<TransactionFilter>::from_query(
+            "address=kromernya1,kromernya2&type=transfer&since=2025-01-01T00:00:00Z&min_amount=1000",
+        )
+        .expect("Failed to deserialize filter")
+        .into_inner();
+
+        assert_eq!(filter.addresses, vec!["kromernya1", "kromernya2"]);
+        assert_eq!(filter.transaction_type, Some(TransactionType::Transfer));
+        assert!(filter.since.is_some());
+        assert_eq!(filter.min_amount, Some(rust_decimal_macros::dec!(1000)));
+    }
+
+    #[test]
+    fn test_parse_common_meta() {
+        let meta = CommonMeta::parse(
+            "shop@store.kst;invoice=abc123; memo = a=b ;thanks;return=pay@me.kst",
+        );
+
+        assert_eq!(meta.recipient, Some("shop@store.kst".to_owned()));
+        assert_eq!(meta.metaname, Some("shop".to_owned()));
+        assert_eq!(meta.name, Some("store".to_owned()));
+        assert_eq!(meta.get("invoice"), Some("abc123"));
+        assert_eq!(meta.get("memo"), Some("a=b"));
+        assert_eq!(meta.return_target, Some("pay@me.kst".to_owned()));
+        assert_eq!(meta.values, vec!["thanks".to_owned()]);
+    }
+
+    #[test]
+    fn test_common_meta_name_target_only_first() {
+        let meta = CommonMeta::parse("hello;store.kst");
+
+        assert_eq!(meta.recipient, None);
+        assert_eq!(
+            meta.values,
+            vec!["hello".to_owned(), "store.kst".to_owned()]
+        );
+        assert_eq!(CommonMeta::target_name("shop@store.kst"), Some("store"));
+        assert!(!CommonMeta::is_name_target("kromernya1"));
+    }
+
+    #[test]
+    fn test_refund_metadata() {
+        let refunded = Thing::from(("transaction", "abc"));
+        let metadata = refund_metadata(
+            Some("shop@store.kst"),
+            &refunded,
+            &[("error", "Out of stock; sorry"), ("message", "")],
+        );
+
+        assert_eq!(
+            metadata,
+            "shop@store.kst;ref=transaction:abc;type=refund;error=Out of stock, sorry"
+        );
+    }
+
+    #[test]
+    fn test_balance_change() {
+        use rust_decimal_macros::dec;
+
+        let alice = Thing::from(("wallet", "alice"));
+        let bob = Thing::from(("wallet", "bob"));
+        let mint = Thing::from(("wallet", "mint"));
+        let transaction = |transaction_type, from: &Thing, to: Thing, recipient| Model {
+            id: None,
+            amount: dec!(10),
+            from: from.clone(),
+            metadata: None,
+            timestamp: Datetime::default(),
+            to,
+            transaction_type,
+            name: None,
+            refund_of: None,
+            recipient,
+            fee_of: None,
+        };
+
+        let transfer = transaction(TransactionType::Transfer, &alice, bob.clone(), None);
+        assert_eq!(transfer.balance_change(&alice), dec!(-10));
+        assert_eq!(transfer.balance_change(&bob), dec!(10));
+        assert_eq!(transfer.balance_change(&mint), Decimal::ZERO);
+
+        // Payments to a name credit whoever owned it at the time
+        let to_name = transaction(
+            TransactionType::Transfer,
+            &alice,
+            Thing::from(("name", "store")),
+            Some(bob.clone()),
+        );
+        assert_eq!(to_name.balance_change(&bob), dec!(10));
+
+        let mint_tx = transaction(TransactionType::Mint, &mint, alice.clone(), None);
+        assert_eq!(mint_tx.balance_change(&alice), dec!(10));
+        assert_eq!(mint_tx.balance_change(&mint), Decimal::ZERO);
+
+        let burn = transaction(TransactionType::Burn, &alice, mint.clone(), None);
+        assert_eq!(burn.balance_change(&alice), dec!(-10));
+        assert_eq!(burn.balance_change(&mint), Decimal::ZERO);
+
+        let purchase = transaction(
+            TransactionType::NamePurchase,
+            &alice,
+            Thing::from(("name", "store")),
+            Some(alice.clone()),
+        );
+        assert_eq!(purchase.balance_change(&alice), dec!(-10));
+    }
+}
@@ -1,3 +1,6 @@
+use std::env;
+
+use once_cell::sync::Lazy;
 use surrealdb::{
     engine::any::Any,
     sql::{Datetime, Id, Thing},
@@ -6,9 +9,145 @@ use surrealdb::{
 
 use rust_decimal::Decimal;
 
-use super::{serialize_table_opt, CountResponse};
+use super::transaction::ENSURE_MINT_WALLET;
+use super::{player, serialize_table, serialize_table_opt, CountResponse, SupplyResponse};
+use crate::database::cursor::{order_clause, Cursor, CursorPage, CursorPosition, Cursored};
+use crate::errors::{player::PlayerError, wallet::WalletError, KromerError};
+use crate::fees::FEE_SCHEDULE;
 use crate::routes::PaginationParams;
 
+/// Address of the system wallet minted money is sent from and burned money is sent to.
+/// Its balance never changes, `total_out` is the total amount minted and `total_in` the total amount burned.
+pub const MINT_ADDRESS: &str = "serverwelf";
+
+/// Role of a player on a wallet, stored on the `owns` edge.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletRole {
+    /// Can spend without limit and manage the wallet.
+    #[default]
+    Owner,
+    /// Can spend up to their spending limit.
+    Spender,
+    /// Can only see the wallet.
+    Viewer,
+}
+
+/// A wallet owned by a player, with their role and whether it is the primary wallet payments to the player go to.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct OwnedWallet {
+    #[serde(flatten)]
+    pub wallet: Model,
+    pub primary: bool,
+    pub role: WalletRole,
+}
+
+/// Whether frozen wallets can still receive transfers.
+static FROZEN_WALLETS_RECEIVE: Lazy<bool> =
+    Lazy::new(|| env::var("FROZEN_WALLETS_RECEIVE").map_or(true, |value| value != "false"));
+
+/// Period over which the transfers of a member count towards their spending limit.
+static SPENDING_LIMIT_WINDOW: Lazy<chrono::Duration> = Lazy::new(|| {
+    let hours = env::var("SPENDING_LIMIT_WINDOW_HOURS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(24);
+    chrono::Duration::hours(hours)
+});
+
+/// Why and since when a wallet is frozen.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct WalletFreeze {
+    pub reason: String,
+    pub frozen_at: Datetime,
+}
+
+/// Policy requiring transfers from a wallet to be approved by several of its members.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct MultisigPolicy {
+    /// Transfers of at least this amount need approval.
+    pub threshold: Decimal,
+    /// Amount of members, owners or spenders, that have to approve.
+    pub approvals: u32,
+}
+
+impl MultisigPolicy {
+    pub fn requires_approval(&self, amount: Decimal) -> bool {
+        amount >= self.threshold
+    }
+}
+
+/// A member of a wallet.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WalletMember {
+    #[serde(serialize_with = "serialize_table")]
+    pub player: Thing,
+    pub name: String,
+    pub role: WalletRole,
+    /// Maximum amount the member can send within the spending limit window, if any.
+    pub spending_limit: Option<Decimal>,
+}
+
+/// A wallet a transfer was authorized to be sent from, see [`Model::authorize_sender`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sender {
+    pub wallet: Model,
+    /// The member sending from a wallet that isn't their own.
+    pub spender: Option<Spender>,
+}
+
+/// A member sending from a wallet, whose transfers count towards their spending limit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spender {
+    pub player: Thing,
+    pub spending_limit: Option<Decimal>,
+}
+
+impl Spender {
+    /// Start of the window the transfers of a member count towards their spending limit in.
+    pub fn window_start() -> Datetime {
+        Datetime::from(chrono::Utc::now() - *SPENDING_LIMIT_WINDOW)
+    }
+
+    /// Get the amount the member sent from a wallet within the spending limit window, fees included.
+    pub async fn spent(
+        &self,
+        db: &Surreal<Any>,
+        wallet: Thing,
+    ) -> Result<Decimal, surrealdb::Error> {
+        let q = "RETURN math::sum(SELECT VALUE amount FROM transaction WHERE from = $wallet AND spent_by = $player AND timestamp > $since) ?? 0dec;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("player", self.player.clone()))
+            .bind(("since", Self::window_start()))
+            .await?;
+        let spent: Option<Decimal> = response.take(0)?;
+
+        Ok(spent.unwrap_or_default())
+    }
+}
+
+/// A newly created wallet with its password, which can't be retrieved later.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct CreatedWallet {
+    pub address: String,
+    pub password: String,
+    pub wallet: Model,
+}
+
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct Model {
     #[serde(
@@ -18,12 +157,20 @@ pub struct Model {
     pub id: Option<Thing>,
     pub address: String,
     pub balance: Decimal,
+    /// The balance minus the active holds on the wallet, computed by the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available: Option<Decimal>,
     pub created_at: Datetime,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hash: Option<String>, // We dont want to retrieve the hash all the time.
     pub is_shared: bool,
     pub total_in: Decimal,
     pub total_out: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigPolicy>,
+    /// Set while the wallet is frozen by an admin.
+    #[serde(default)]
+    pub frozen: Option<WalletFreeze>,
 }
 
 impl Model {
@@ -106,6 +253,519 @@ impl Model {
         Ok(models)
     }
 
+    /// Get a page of wallets, newest first, omitting hash.
+    pub async fn page(
+        db: &Surreal<Any>,
+        limit: u64,
+        position: Option<&CursorPosition>,
+    ) -> Result<CursorPage<Model>, surrealdb::Error> {
+        let condition = position
+            .map(|position| format!("WHERE {}", position.condition("created_at")))
+            .unwrap_or_default();
+        let q = format!(
+            "SELECT * OMIT hash FROM wallet {condition} {} LIMIT $limit;",
+            order_clause("created_at", position)
+        );
+
+        let cursor = position.map(|position| position.cursor.clone());
+        let mut response = db
+            .query(q)
+            .bind(("limit", limit + 1))
+            .bind(("cursor_time", cursor.as_ref().map(|c| c.time.clone())))
+            .bind(("cursor_id", cursor.map(|c| c.id)))
+            .await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(CursorPage::new(models, limit as usize, position))
+    }
+
+    /// Get the wallets owned by a player, oldest first, omitting hash.
+    pub async fn get_by_player(
+        db: &Surreal<Any>,
+        player: Thing,
+    ) -> Result<Vec<OwnedWallet>, surrealdb::Error> {
+        let q = r#"
+            SELECT *,
+                array::len(<-owns[WHERE in = $player AND primary = true]) > 0 AS primary,
+                (<-owns[WHERE in = $player].role)[0] ?? 'owner' AS role
+            OMIT hash FROM $player->owns->wallet ORDER BY created_at ASC;
+        "#;
+
+        let mut response = db.query(q).bind(("player", player)).await?;
+        let models: Vec<OwnedWallet> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Get the wallet payments to a player go to.
+    pub async fn get_primary_for_player(
+        db: &Surreal<Any>,
+        name: String,
+    ) -> Result<Model, KromerError> {
+        let player = player::Model::get_by_name(db, name.clone())
+            .await?
+            .ok_or(KromerError::Player(PlayerError::NotFound))?;
+        let player_id = player
+            .id
+            .ok_or(KromerError::Internal("Player is missing its id"))?;
+
+        let wallets = Self::get_by_player(db, player_id).await?;
+
+        Ok(OwnedWallet::primary(wallets, player.name)?)
+    }
+
+    /// Get the wallet payments to a player go to, from the Minecraft UUID of the player.
+    pub async fn get_primary_for_player_uuid(
+        db: &Surreal<Any>,
+        mc_uuid: &str,
+    ) -> Result<Model, KromerError> {
+        let player = player::Model::get_partial(db, mc_uuid)
+            .await?
+            .ok_or(KromerError::Player(PlayerError::NotFound))?;
+        let player_id = player
+            .id
+            .ok_or(KromerError::Internal("Player is missing its id"))?;
+
+        let wallets = Self::get_by_player(db, player_id).await?;
+
+        Ok(OwnedWallet::primary(wallets, player.name)?)
+    }
+
+    /// Create a new wallet owned by a player, optionally making it their primary wallet.
+    /// A starting balance is minted to it in the same database transaction, so the wallet is never
+    /// created without it.
+    pub async fn create_for_player(
+        db: &Surreal<Any>,
+        player: Thing,
+        primary: bool,
+        starting_balance: Decimal,
+        metadata: Option<String>,
+    ) -> Result<CreatedWallet, KromerError> {
+        Self::create_owned(db, player, primary, false, starting_balance, metadata)
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::FailedCreate))
+    }
+
+    /// Create the first wallet of a player as their primary wallet, like [`Model::create_for_player`].
+    /// Returns `None` without creating anything if the player already owns a wallet, which is checked in the same
+    /// database transaction so concurrent requests can't create two.
+    pub async fn create_first_for_player(
+        db: &Surreal<Any>,
+        player: Thing,
+        starting_balance: Decimal,
+        metadata: Option<String>,
+    ) -> Result<Option<CreatedWallet>, KromerError> {
+        Self::create_owned(db, player, true, true, starting_balance, metadata).await
+    }
+
+    async fn create_owned(
+        db: &Surreal<Any>,
+        player: Thing,
+        primary: bool,
+        first_only: bool,
+        starting_balance: Decimal,
+        metadata: Option<String>,
+    ) -> Result<Option<CreatedWallet>, KromerError> {
+        let q = format!(
+            r#"
+            BEGIN TRANSACTION;
+            IF $first_only AND (SELECT VALUE id FROM owns WHERE in = $player LIMIT 1) {{ RETURN NONE }};
+            LET $created = fn::create_wallet(0);
+            LET $wallet = $created.wallet.id;
+            IF $primary {{ UPDATE owns SET primary = false WHERE in = $player }};
+            RELATE $player->owns->$wallet SET primary = $primary, role = 'owner';
+            IF $starting_balance > 0 {{
+                {ENSURE_MINT_WALLET}
+                CREATE transaction CONTENT {{ from: wallet:mint, to: $wallet, amount: $starting_balance, metadata: $metadata, transaction_type: 'mint' }};
+            }};
+            RETURN {{ address: $created.address, password: $created.password, wallet: $wallet.* }};
+            COMMIT TRANSACTION;
+        "#
+        );
+
+        let mut response = db
+            .query(q)
+            .bind(("player", player))
+            .bind(("primary", primary))
+            .bind(("first_only", first_only))
+            .bind(("starting_balance", starting_balance))
+            .bind(("metadata", metadata))
+            .bind(("mint_address", MINT_ADDRESS))
+            .await?;
+        let index = response.num_statements() - 1;
+        let created: Option<CreatedWallet> = response.take(index)?;
+
+        Ok(created)
+    }
+
+    /// Make a wallet owned by a player their primary wallet, unsetting the previous one.
+    pub async fn set_primary(
+        db: &Surreal<Any>,
+        player: Thing,
+        wallet: &Model,
+    ) -> Result<(), KromerError> {
+        let owned = Self::get_by_player(db, player.clone()).await?;
+        let is_owner = owned
+            .iter()
+            .any(|owned| owned.wallet.id == wallet.id && owned.role == WalletRole::Owner);
+        if !is_owner {
+            return Err(KromerError::Player(PlayerError::WalletNotOwned(
+                wallet.address.clone(),
+            )));
+        }
+
+        let q = r#"
+            BEGIN TRANSACTION;
+            UPDATE owns SET primary = (out = $wallet) WHERE in = $player;
+            COMMIT TRANSACTION;
+        "#;
+
+        db.query(q)
+            .bind(("player", player))
+            .bind(("wallet", wallet.id.clone()))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Get the members of a wallet.
+    pub async fn get_members(
+        db: &Surreal<Any>,
+        wallet: Thing,
+    ) -> Result<Vec<WalletMember>, surrealdb::Error> {
+        let q = "SELECT in AS player, in.name AS name, role ?? 'owner' AS role, spending_limit FROM owns WHERE out = $wallet;";
+
+        let mut response = db.query(q).bind(("wallet", wallet)).await?;
+        let members: Vec<WalletMember> = response.take(0)?;
+
+        Ok(members)
+    }
+
+    /// Add a player to a wallet or change their role and spending limit, marking the wallet as shared.
+    pub async fn set_member(
+        db: &Surreal<Any>,
+        wallet: &Model,
+        player: Thing,
+        role: WalletRole,
+        spending_limit: Option<Decimal>,
+    ) -> Result<Vec<WalletMember>, KromerError> {
+        let wallet_id = wallet
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+        let members = Self::get_members(db, wallet_id.clone()).await?;
+        let is_last_owner = members
+            .iter()
+            .all(|member| member.player == player || member.role != WalletRole::Owner);
+        if role != WalletRole::Owner && is_last_owner {
+            return Err(KromerError::Wallet(WalletError::LastOwner));
+        }
+
+        let q = r#"
+            BEGIN TRANSACTION;
+            LET $edge = (SELECT VALUE id FROM owns WHERE in = $player AND out = $wallet)[0];
+            IF $edge {
+                UPDATE $edge SET role = $role, spending_limit = $spending_limit;
+            } ELSE {
+                RELATE $player->owns->$wallet SET primary = false, role = $role, spending_limit = $spending_limit;
+            };
+            UPDATE $wallet SET is_shared = array::len(<-owns) > 1;
+            COMMIT TRANSACTION;
+        "#;
+
+        db.query(q)
+            .bind(("wallet", wallet_id.clone()))
+            .bind(("player", player))
+            .bind(("role", role))
+            .bind(("spending_limit", spending_limit))
+            .await?
+            .check()?;
+
+        Ok(Self::get_members(db, wallet_id).await?)
+    }
+
+    /// Remove a player from a wallet. The last owner of a wallet can't be removed.
+    pub async fn remove_member(
+        db: &Surreal<Any>,
+        wallet: &Model,
+        player: Thing,
+    ) -> Result<Vec<WalletMember>, KromerError> {
+        let wallet_id = wallet
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+        let members = Self::get_members(db, wallet_id.clone()).await?;
+        if !members.iter().any(|member| member.player == player) {
+            return Err(KromerError::Player(PlayerError::WalletNotOwned(
+                wallet.address.clone(),
+            )));
+        }
+        if members
+            .iter()
+            .all(|member| member.player == player || member.role != WalletRole::Owner)
+        {
+            return Err(KromerError::Wallet(WalletError::LastOwner));
+        }
+
+        let q = r#"
+            BEGIN TRANSACTION;
+            DELETE owns WHERE in = $player AND out = $wallet;
+            UPDATE $wallet SET is_shared = array::len(<-owns) > 1;
+            COMMIT TRANSACTION;
+        "#;
+
+        db.query(q)
+            .bind(("wallet", wallet_id.clone()))
+            .bind(("player", player))
+            .await?
+            .check()?;
+
+        Ok(Self::get_members(db, wallet_id).await?)
+    }
+
+    /// Get the wallet a transfer is sent from.
+    /// Without `from` this is the wallet the password belongs to. With `from`, the password may instead belong to a wallet
+    /// owned by a member of `from`, who has to be allowed to spend `amount` from it. Spenders can send up to their
+    /// spending limit within the spending limit window, counting what they already sent.
+    /// Transfers that need approval by the members of the wallet are refused.
+    /// When a transfer to `to` is charged a fee, spending limits and approval thresholds apply to the amount plus the fee.
+    pub async fn authorize_sender(
+        db: &Surreal<Any>,
+        password: String,
+        from: Option<String>,
+        to: Option<&Model>,
+        amount: Decimal,
+    ) -> Result<Sender, KromerError> {
+        let credential = Self::verify(db, password)
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+
+        let (wallet, memberships) = match from {
+            Some(from) if from != credential.address => {
+                let wallet = Self::get_by_address(db, from)
+                    .await?
+                    .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+                let memberships = Self::get_memberships(db, &credential, &wallet).await?;
+
+                (wallet, Some(memberships))
+            }
+            _ => (credential, None),
+        };
+
+        Self::authorize_spend(db, wallet, memberships, to, amount).await
+    }
+
+    /// Check that `amount` can be sent from `wallet` to `to`, by the wallet itself without
+    /// `memberships`, or else by a member with one of them. See [`Model::authorize_sender`].
+    pub async fn authorize_spend(
+        db: &Surreal<Any>,
+        wallet: Model,
+        memberships: Option<Vec<WalletMember>>,
+        to: Option<&Model>,
+        amount: Decimal,
+    ) -> Result<Sender, KromerError> {
+        let fee = match to {
+            Some(to) => FEE_SCHEDULE
+                .quote(db, &wallet, to, amount)
+                .await?
+                .map_or(Decimal::ZERO, |fee| fee.amount),
+            None => Decimal::ZERO,
+        };
+        let total = amount + fee;
+
+        let spender = match memberships {
+            Some(memberships) => {
+                Some(Self::authorize_member(db, &wallet, memberships, total).await?)
+            }
+            None => None,
+        };
+
+        if wallet
+            .multisig
+            .as_ref()
+            .is_some_and(|policy| policy.requires_approval(total))
+        {
+            return Err(KromerError::Wallet(WalletError::ApprovalRequired));
+        }
+
+        Ok(Sender { wallet, spender })
+    }
+
+    /// Get the membership `total` can be sent from `wallet` with, owners first.
+    async fn authorize_member(
+        db: &Surreal<Any>,
+        wallet: &Model,
+        mut memberships: Vec<WalletMember>,
+        total: Decimal,
+    ) -> Result<Spender, KromerError> {
+        let wallet_id = wallet
+            .id
+            .clone()
+            .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+        memberships.sort_by_key(|member| member.role);
+
+        let mut is_spender = false;
+        for member in memberships {
+            let spender = Spender {
+                player: member.player,
+                spending_limit: member.spending_limit,
+            };
+            match member.role {
+                WalletRole::Owner => {
+                    return Ok(Spender {
+                        spending_limit: None,
+                        ..spender
+                    })
+                }
+                WalletRole::Spender => {
+                    is_spender = true;
+                    let Some(limit) = spender.spending_limit else {
+                        return Ok(spender);
+                    };
+                    if spender.spent(db, wallet_id.clone()).await? + total <= limit {
+                        return Ok(spender);
+                    }
+                }
+                WalletRole::Viewer => {}
+            }
+        }
+
+        Err(KromerError::Wallet(if is_spender {
+            WalletError::SpendingLimitExceeded
+        } else {
+            WalletError::NotAllowed
+        }))
+    }
+
+    /// Get the membership on `wallet` of the player owning the wallet the password belongs to, with their highest role.
+    pub async fn get_member_by_password(
+        db: &Surreal<Any>,
+        password: String,
+        wallet: &Model,
+    ) -> Result<WalletMember, KromerError> {
+        let credential = Self::verify(db, password)
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+
+        let memberships = Self::get_memberships(db, &credential, wallet).await?;
+
+        memberships
+            .into_iter()
+            .min_by_key(|member| member.role)
+            .ok_or(KromerError::Wallet(WalletError::NotAllowed))
+    }
+
+    /// Get the memberships on `wallet` of the players owning `credential`.
+    async fn get_memberships(
+        db: &Surreal<Any>,
+        credential: &Model,
+        wallet: &Model,
+    ) -> Result<Vec<WalletMember>, surrealdb::Error> {
+        let q = r#"
+            LET $players = (SELECT VALUE in FROM owns WHERE out = $credential AND (role ?? 'owner') = 'owner');
+            SELECT in AS player, in.name AS name, role ?? 'owner' AS role, spending_limit FROM owns WHERE out = $wallet AND in IN $players;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("credential", credential.id.clone()))
+            .bind(("wallet", wallet.id.clone()))
+            .await?;
+        let memberships: Vec<WalletMember> = response.take(1)?;
+
+        Ok(memberships)
+    }
+
+    /// Set or remove the policy requiring member approval for large transfers from a wallet.
+    pub async fn set_multisig(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        policy: Option<MultisigPolicy>,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let q = "UPDATE ONLY $wallet SET multisig = $policy RETURN AFTER;";
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("policy", policy))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Resolve the recipient of a transfer. `@PlayerName` resolves to the primary wallet of that player, anything else is an address.
+    pub async fn resolve_recipient(db: &Surreal<Any>, to: &str) -> Result<Model, KromerError> {
+        match to.strip_prefix('@') {
+            Some(name) => Self::get_primary_for_player(db, name.to_string()).await,
+            None => Self::get_by_address(db, to.to_string())
+                .await?
+                .ok_or(KromerError::Wallet(WalletError::NotFound)),
+        }
+    }
+
+    /// Freeze a wallet, or unfreeze it if no reason is given.
+    pub async fn set_frozen(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        reason: Option<String>,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let q = r#"
+            UPDATE ONLY $wallet SET frozen = IF $reason { { reason: $reason, frozen_at: time::now() } } ELSE { NONE }
+            RETURN AFTER OMIT hash;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("reason", reason))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// The balance that can be spent, which excludes funds reserved by holds.
+    pub fn available_balance(&self) -> Decimal {
+        self.available.unwrap_or(self.balance)
+    }
+
+    /// Make sure the wallet can send money, which frozen wallets can't.
+    pub fn ensure_can_send(&self) -> Result<(), WalletError> {
+        match self.frozen {
+            Some(_) => Err(WalletError::Frozen(self.address.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Make sure the wallet can receive money. Whether frozen wallets can is configurable.
+    pub fn ensure_can_receive(&self) -> Result<(), WalletError> {
+        match self.frozen {
+            Some(_) if !*FROZEN_WALLETS_RECEIVE => Err(WalletError::Frozen(self.address.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace the key of a wallet with a new random one, returning the new key.
+    pub async fn rotate_key(db: &Surreal<Any>, wallet: Thing) -> Result<String, KromerError> {
+        let q = r#"
+            BEGIN TRANSACTION;
+            LET $password = rand::string(16);
+            UPDATE $wallet SET hash = crypto::argon2::generate($password);
+            RETURN $password;
+            COMMIT TRANSACTION;
+        "#;
+
+        let mut response = db.query(q).bind(("wallet", wallet)).await?;
+        let index = response.num_statements() - 1;
+        let password: Option<String> = response.take(index)?;
+
+        password.ok_or(KromerError::Internal("Unable to get rotated key"))
+    }
+
     /// Verify the password of a wallet, returning the given wallet if it exists.
     pub async fn verify(
         db: &Surreal<Any>,
@@ -152,4 +812,46 @@ impl Model {
 
         Ok(count.count)
     }
+
+    /// Get the sum of all wallet balances, i.e. the money in circulation
+    pub async fn supply(db: &Surreal<Any>) -> Result<Decimal, surrealdb::Error> {
+        let q =
+            "(SELECT math::sum(balance) AS supply FROM wallet GROUP ALL)[0] or { supply: 0dec }";
+
+        let mut response = db.query(q).await?;
+        let supply: Option<SupplyResponse> = response.take(0)?;
+        let supply = supply.unwrap_or_default(); // Same deal as `count`, the `or` statement makes sure we get something back.
+
+        Ok(supply.supply)
+    }
+}
+
+impl OwnedWallet {
+    /// Pick the primary wallet of a player from the wallets they own.
+    /// A player owning a single wallet doesn't need to designate it as primary, wallets they are only a member of are never picked.
+    pub fn primary(wallets: Vec<OwnedWallet>, player_name: String) -> Result<Model, PlayerError> {
+        let mut wallets: Vec<OwnedWallet> = wallets
+            .into_iter()
+            .filter(|owned| owned.role == WalletRole::Owner)
+            .collect();
+
+        if let Some(index) = wallets.iter().position(|owned| owned.primary) {
+            return Ok(wallets.swap_remove(index).wallet);
+        }
+
+        match wallets.len() {
+            0 => Err(PlayerError::NoWallet(player_name)),
+            1 => Ok(wallets.remove(0).wallet),
+            _ => Err(PlayerError::NoPrimaryWallet(player_name)),
+        }
+    }
+}
+
+impl Cursored for Model {
+    fn cursor(&self) -> Option<Cursor> {
+        Some(Cursor {
+            time: self.created_at.clone(),
+            id: self.id.clone()?,
+        })
+    }
 }
@@ -0,0 +1,332 @@
+use std::env;
+
+use chrono::{Duration, Utc};
+use once_cell::sync::Lazy;
+use surrealdb::{
+    engine::any::Any,
+    sql::{Datetime, Id, Thing},
+    Surreal,
+};
+
+use super::{serialize_table, serialize_table_opt};
+use crate::errors::{webhook::WebhookError, KromerError};
+use crate::routes::PaginationParams;
+use crate::webhooks;
+
+/// How many times a delivery is attempted before it is moved to the dead-letter list.
+pub static WEBHOOK_MAX_ATTEMPTS: Lazy<u32> = Lazy::new(|| {
+    env::var("WEBHOOK_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(8)
+});
+
+/// Delay before the first retry, doubled on every following one.
+static WEBHOOK_RETRY_BASE: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("WEBHOOK_RETRY_BASE_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(10);
+    Duration::seconds(seconds)
+});
+
+/// Kind of wallet activity a webhook can subscribe to.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// Transactions received by the wallet.
+    Incoming,
+    /// Transactions sent by the wallet.
+    Outgoing,
+    /// Name purchases, transfers and record updates involving the wallet.
+    Name,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Gave up after too many failed attempts.
+    Dead,
+}
+
+/// An HTTP endpoint notified about the activity of a wallet.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Model {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
+    pub wallet: Thing,
+    pub url: String,
+    /// Key the request bodies are signed with. Only shown once, when the webhook is created.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: Datetime,
+}
+
+/// A single notification of a transaction to a webhook.
+#[derive(Clone, Debug, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+pub struct Delivery {
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_table_opt"
+    )]
+    pub id: Option<Thing>,
+    #[serde(serialize_with = "serialize_table")]
+    pub webhook: Thing,
+    pub event: WebhookEvent,
+    #[serde(serialize_with = "serialize_table")]
+    pub transaction: Thing,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: Datetime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// HTTP status of the last response, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_status: Option<u16>,
+    pub created_at: Datetime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<Datetime>,
+}
+
+impl Model {
+    /// Get a webhook from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Model>, surrealdb::Error> {
+        let thing = Thing::from(("webhook", Id::from(id.as_ref())));
+        let q = "SELECT * FROM webhook WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Model> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// Get the webhooks of a wallet, oldest first.
+    pub async fn get_by_wallet(
+        db: &Surreal<Any>,
+        wallet: Thing,
+    ) -> Result<Vec<Model>, surrealdb::Error> {
+        let q = "SELECT * FROM webhook WHERE wallet = $wallet ORDER BY created_at ASC;";
+
+        let mut response = db.query(q).bind(("wallet", wallet)).await?;
+        let models: Vec<Model> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Register a webhook for a wallet. A random secret is generated if none is given.
+    pub async fn create(
+        db: &Surreal<Any>,
+        wallet: Thing,
+        url: String,
+        secret: Option<String>,
+        mut events: Vec<WebhookEvent>,
+    ) -> Result<Model, KromerError> {
+        webhooks::check_url(&url).await?;
+
+        events.sort();
+        events.dedup();
+        if events.is_empty() {
+            return Err(KromerError::Webhook(WebhookError::NoEvents));
+        }
+
+        let q = r#"
+            CREATE ONLY webhook CONTENT {
+                wallet: $wallet,
+                url: $url,
+                secret: $secret ?? rand::string(32),
+                events: $events,
+            };
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("wallet", wallet))
+            .bind(("url", url))
+            .bind(("secret", secret))
+            .bind(("events", events))
+            .await?;
+        let model: Option<Model> = response.take(0)?;
+
+        model.ok_or(KromerError::Internal("Unable to get created webhook"))
+    }
+
+    /// Remove a webhook. Its undelivered notifications are dropped as well.
+    pub async fn delete(db: &Surreal<Any>, webhook: Thing) -> Result<(), surrealdb::Error> {
+        let q = r#"
+            BEGIN TRANSACTION;
+            DELETE webhook_delivery WHERE webhook = $webhook AND status = 'pending';
+            DELETE $webhook;
+            COMMIT TRANSACTION;
+        "#;
+        db.query(q).bind(("webhook", webhook)).await?.check()?;
+
+        Ok(())
+    }
+}
+
+impl Delivery {
+    /// Get a delivery from its unique ID, not including the table part
+    pub async fn get_partial<S: AsRef<str>>(
+        db: &Surreal<Any>,
+        id: S,
+    ) -> Result<Option<Delivery>, surrealdb::Error> {
+        let thing = Thing::from(("webhook_delivery", Id::from(id.as_ref())));
+        let q = "SELECT * FROM webhook_delivery WHERE id = $id;";
+
+        let mut response = db.query(q).bind(("id", thing)).await?;
+        let model: Option<Delivery> = response.take(0)?;
+
+        Ok(model)
+    }
+
+    /// List deliveries, newest first, optionally only those of one webhook or with one status.
+    pub async fn list(
+        db: &Surreal<Any>,
+        webhook: Option<Thing>,
+        status: Option<DeliveryStatus>,
+        pagination: &PaginationParams,
+    ) -> Result<Vec<Delivery>, surrealdb::Error> {
+        let limit = pagination.limit.unwrap_or(50);
+        let offset = pagination.offset.unwrap_or(0);
+        let limit = limit.clamp(1, 1000);
+
+        let q = r#"
+            SELECT * FROM webhook_delivery
+            WHERE ($webhook IS NONE OR webhook = $webhook) AND ($status IS NONE OR status = $status)
+            ORDER BY created_at DESC LIMIT $limit START $offset;
+        "#;
+
+        let mut response = db
+            .query(q)
+            .bind(("webhook", webhook))
+            .bind(("status", status))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let models: Vec<Delivery> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Get the deliveries that are due for an attempt, oldest first.
+    pub async fn due(db: &Surreal<Any>, limit: u64) -> Result<Vec<Delivery>, surrealdb::Error> {
+        let q = r#"
+            SELECT * FROM webhook_delivery
+            WHERE status = 'pending' AND next_attempt_at <= time::now()
+            ORDER BY next_attempt_at ASC LIMIT $limit;
+        "#;
+
+        let mut response = db.query(q).bind(("limit", limit)).await?;
+        let models: Vec<Delivery> = response.take(0)?;
+
+        Ok(models)
+    }
+
+    /// Record a successful attempt.
+    pub async fn mark_delivered(
+        &self,
+        db: &Surreal<Any>,
+        status: u16,
+    ) -> Result<(), surrealdb::Error> {
+        let q = r#"
+            UPDATE $id SET
+                status = 'delivered',
+                attempts += 1,
+                last_status = $last_status,
+                last_error = NONE,
+                delivered_at = time::now();
+        "#;
+
+        db.query(q)
+            .bind(("id", self.id.clone()))
+            .bind(("last_status", status))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt, scheduling a retry with exponential backoff or giving up
+    /// once [`WEBHOOK_MAX_ATTEMPTS`] is reached.
+    pub async fn mark_failed(
+        &self,
+        db: &Surreal<Any>,
+        status: Option<u16>,
+        error: String,
+    ) -> Result<(), surrealdb::Error> {
+        let attempts = self.attempts + 1;
+        let dead = attempts >= *WEBHOOK_MAX_ATTEMPTS;
+        let next_attempt_at = Datetime::from(Utc::now() + retry_delay(attempts));
+
+        let q = r#"
+            UPDATE $id SET
+                status = IF $dead { 'dead' } ELSE { 'pending' },
+                attempts = $attempts,
+                next_attempt_at = $next_attempt_at,
+                last_status = $last_status,
+                last_error = $last_error;
+        "#;
+
+        db.query(q)
+            .bind(("id", self.id.clone()))
+            .bind(("dead", dead))
+            .bind(("attempts", attempts))
+            .bind(("next_attempt_at", next_attempt_at))
+            .bind(("last_status", status))
+            .bind(("last_error", error))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Queue a delivery again for an immediate attempt, with a fresh set of retries.
+    pub async fn replay(db: &Surreal<Any>, delivery: Thing) -> Result<Delivery, KromerError> {
+        let q = r#"
+            UPDATE ONLY $id SET
+                status = 'pending',
+                attempts = 0,
+                next_attempt_at = time::now(),
+                delivered_at = NONE
+            RETURN AFTER;
+        "#;
+
+        let mut response = db.query(q).bind(("id", delivery)).await?;
+        let model: Option<Delivery> = response.take(0)?;
+
+        model.ok_or(KromerError::Webhook(WebhookError::DeliveryNotFound))
+    }
+}
+
+/// Delay before the next attempt after the given number of failed attempts.
+pub fn retry_delay(attempts: u32) -> Duration {
+    let factor = 2i32.saturating_pow(attempts.saturating_sub(1).min(16));
+    *WEBHOOK_RETRY_BASE * factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        let base = *WEBHOOK_RETRY_BASE;
+
+        assert_eq!(retry_delay(1), base);
+        assert_eq!(retry_delay(2), base * 2);
+        assert_eq!(retry_delay(4), base * 8);
+        assert_eq!(retry_delay(100), retry_delay(17));
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use surrealdb::{engine::any::Any, sql::Datetime, Surreal};
+
+use super::models::{
+    name::Model as Name, transaction::Model as Transaction, wallet::Model as Wallet,
+};
+use crate::{models::stats::EconomyStats, websockets::utils::datetime::convert_to_iso_string};
+
+/// How long computed statistics are served before they are computed again.
+pub const STATS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+const TOP_MOVERS_LIMIT: u64 = 10;
+
+#[derive(Debug, Default)]
+pub struct StatsCache {
+    cached: Option<(Instant, EconomyStats)>,
+}
+
+impl StatsCache {
+    pub fn new() -> Self {
+        Self { cached: None }
+    }
+
+    /// Get the cached statistics, if they are still fresh.
+    pub fn get(&self) -> Option<EconomyStats> {
+        self.cached
+            .as_ref()
+            .filter(|(computed_at, _)| computed_at.elapsed() < STATS_CACHE_TTL)
+            .map(|(_, stats)| stats.clone())
+    }
+
+    pub fn set(&mut self, stats: EconomyStats) {
+        self.cached = Some((Instant::now(), stats));
+    }
+}
+
+/// Compute economy-wide statistics. This runs several aggregate queries, so callers should go through [`StatsCache`].
+pub async fn economy_stats(db: &Surreal<Any>) -> Result<EconomyStats, surrealdb::Error> {
+    let now = Utc::now();
+    let last_day = Datetime::from(now - chrono::Duration::days(1));
+    let last_week = Datetime::from(now - chrono::Duration::days(7));
+
+    let stats = EconomyStats {
+        supply: Wallet::supply(db).await?,
+        wallets: Wallet::count(db).await?,
+        names: Name::count(db).await?,
+        last_day: Transaction::activity_since(db, last_day.clone()).await?,
+        last_week: Transaction::activity_since(db, last_week).await?,
+        top_movers: Transaction::top_movers_since(db, last_day, TOP_MOVERS_LIMIT).await?,
+        generated_at: convert_to_iso_string(now),
+    };
+
+    Ok(stats)
+}
@@ -0,0 +1,27 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HoldError {
+    #[error("Hold not found")]
+    NotFound,
+
+    #[error("Hold is no longer active")]
+    NotActive,
+
+    #[error("Only the holder can capture or release a hold")]
+    NotHolder,
+
+    #[error("Amount exceeds what remains of the hold")]
+    ExceedsRemaining,
+}
+
+impl error::ResponseError for HoldError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            HoldError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            HoldError::NotActive => actix_web::http::StatusCode::CONFLICT,
+            HoldError::NotHolder => actix_web::http::StatusCode::FORBIDDEN,
+            HoldError::ExceedsRemaining => actix_web::http::StatusCode::BAD_REQUEST,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvoiceError {
+    #[error("Invoice not found")]
+    NotFound,
+
+    #[error("Invoice is no longer unpaid")]
+    NotUnpaid,
+
+    #[error("Only the wallet the invoice is for can manage it")]
+    NotAllowed,
+}
+
+impl error::ResponseError for InvoiceError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            InvoiceError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            InvoiceError::NotUnpaid => actix_web::http::StatusCode::CONFLICT,
+            InvoiceError::NotAllowed => actix_web::http::StatusCode::FORBIDDEN,
+        }
+    }
+}
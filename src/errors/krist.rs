@@ -5,9 +5,23 @@ pub mod name;
 pub mod transaction;
 pub mod websockets;
 
+use std::env;
+
 use actix_web::{error, http::StatusCode, HttpResponse};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+use crate::errors::{
+    name::NameError as KromerNameError, player::PlayerError,
+    transaction::TransactionError as KromerTransactionError, wallet::WalletError, KromerError,
+};
+
+/// In Krist, error responses are always sent with status code 200 because of a long standing bug that was never fixed.
+/// Some legacy programs check whether `http.get` succeeded before parsing `ok: false`, so this can be turned on with
+/// `KRIST_COMPAT_STATUS_CODES=true` to mimic that behaviour. Otherwise the status code matching the error is used.
+static COMPAT_STATUS_CODES: Lazy<bool> =
+    Lazy::new(|| env::var("KRIST_COMPAT_STATUS_CODES").is_ok_and(|value| value == "true"));
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct KristErrorResponse {
     pub ok: bool,
@@ -39,6 +53,24 @@ pub enum KristError {
 
     #[error("{0}")]
     Custom(&'static str),
+
+    /// An error of the shared business logic without a Krist equivalent, with its own error type and status code.
+    #[error("{message}")]
+    Kromer {
+        error_type: &'static str,
+        message: String,
+        status: StatusCode,
+    },
+}
+
+impl KristError {
+    fn kromer(error_type: &'static str, error: impl error::ResponseError) -> Self {
+        KristError::Kromer {
+            error_type,
+            message: error.to_string(),
+            status: error.status_code(),
+        }
+    }
 }
 
 pub trait KristErrorExt {
@@ -56,15 +88,37 @@ impl KristErrorExt for KristError {
             KristError::WebSocket(e) => e.error_type(),
             KristError::Database(_) => "internal_server_error",
             KristError::Custom(e) => e, // Same way as krist, where message is the error type when no message type is given
+            KristError::Kromer { error_type, .. } => error_type,
         }
     }
 }
 
+/// Get the status code an error response should be sent with, depending on whether Krist compatible status codes are enabled.
+pub fn response_status(status: StatusCode) -> StatusCode {
+    if *COMPAT_STATUS_CODES {
+        StatusCode::OK
+    } else {
+        status
+    }
+}
+
+/// Build the JSON response for a Krist error.
+pub fn krist_error_response<E>(error: &E) -> HttpResponse
+where
+    E: KristErrorExt + error::ResponseError,
+{
+    let response = KristErrorResponse {
+        ok: false,
+        error: error.error_type(),
+        message: error.to_string(),
+        info: None, // Unsure wheter or not this is right
+    };
+
+    HttpResponse::build(response_status(error.status_code())).json(response)
+}
+
 impl error::ResponseError for KristError {
     fn status_code(&self) -> StatusCode {
-        // TODO: Evaluate whether or not programs might break when returning the correct error code or not.
-        //       In Krist, responses are always error code 200 because of a long standing bug.
-        //       For some reason, that bug was never fixed and is just set there for forever, pretty stupid if you ask me.
         match self {
             KristError::Address(e) => e.status_code(),
             KristError::Generic(e) => e.status_code(),
@@ -73,6 +127,7 @@ impl error::ResponseError for KristError {
             KristError::WebSocket(e) => e.status_code(),
             KristError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
             KristError::Custom(_) => StatusCode::BAD_REQUEST,
+            KristError::Kromer { status, .. } => *status,
         }
     }
 
@@ -83,25 +138,86 @@ impl error::ResponseError for KristError {
             KristError::Name(e) => e.error_response(),
             KristError::Transaction(e) => e.error_response(),
             KristError::WebSocket(e) => e.error_response(),
-            KristError::Database(_) => {
-                let error = KristErrorResponse {
-                    ok: false,
-                    error: self.error_type(),
-                    message: self.to_string(),
-                    info: None,
-                };
-
-                HttpResponse::build(self.status_code()).json(error)
+            KristError::Database(_) | KristError::Custom(_) | KristError::Kromer { .. } => {
+                krist_error_response(self)
             }
-            KristError::Custom(e) => {
-                let error = KristErrorResponse {
-                    ok: false,
-                    error: e,
-                    message: e.to_string(),
-                    info: None,
-                };
+        }
+    }
+}
 
-                HttpResponse::build(StatusCode::BAD_REQUEST).json(error)
+/// Lets Krist routes reuse business logic that reports errors as [`KromerError`].
+/// Every error is mapped explicitly, so new errors have to be given a Krist error type.
+impl From<KromerError> for KristError {
+    fn from(error: KromerError) -> Self {
+        match error {
+            KromerError::NotFound => KristError::kromer("not_found", error),
+            KromerError::Database(e) => KristError::Database(e),
+            KromerError::Wallet(e) => match e {
+                WalletError::InvalidPassword => {
+                    KristError::Address(address::AddressError::AuthFailed)
+                }
+                WalletError::Frozen(address) => {
+                    KristError::Address(address::AddressError::Frozen(address))
+                }
+                WalletError::NotFound => KristError::kromer("address_not_found", e),
+                WalletError::NotAllowed => KristError::kromer("not_allowed", e),
+                WalletError::SpendingLimitExceeded => {
+                    KristError::kromer("spending_limit_exceeded", e)
+                }
+                WalletError::ApprovalRequired => KristError::kromer("approval_required", e),
+                WalletError::LastOwner => KristError::kromer("last_owner", e),
+                WalletError::FailedCreate | WalletError::FailedTransfer => {
+                    KristError::kromer("internal_server_error", e)
+                }
+            },
+            KromerError::Name(e) => match e {
+                KromerNameError::Taken(name) => KristError::Name(name::NameError::NameTaken(name)),
+                KromerNameError::NotOwner(name) => {
+                    KristError::Name(name::NameError::NotNameOwner(name))
+                }
+                KromerNameError::InvalidName(_) => {
+                    KristError::Generic(generic::GenericError::InvalidParameter("name".into()))
+                }
+                KromerNameError::NotFound => KristError::kromer("name_not_found", e),
+                KromerNameError::FailedTransfer => KristError::kromer("internal_server_error", e),
+                KromerNameError::Expired(_) => KristError::kromer("name_expired", e),
+                KromerNameError::RentDisabled => KristError::kromer("name_rent_disabled", e),
+            },
+            KromerError::Transaction(e) => match e {
+                KromerTransactionError::InsufficientFunds => {
+                    KristError::Transaction(transaction::TransactionError::InsufficientFunds)
+                }
+                KromerTransactionError::NotFound => {
+                    KristError::Transaction(transaction::TransactionError::NotFound)
+                }
+                KromerTransactionError::InvalidAmount
+                | KromerTransactionError::RefundExceedsAmount => {
+                    KristError::Generic(generic::GenericError::InvalidParameter("amount".into()))
+                }
+                KromerTransactionError::FailedCreate => {
+                    KristError::kromer("internal_server_error", e)
+                }
+            },
+            KromerError::Player(e) => {
+                let error_type = match e {
+                    PlayerError::NotFound => "player_not_found",
+                    PlayerError::NoWallet(_) => "player_has_no_wallet",
+                    PlayerError::NoPrimaryWallet(_) => "no_primary_wallet",
+                    PlayerError::WalletNotOwned(_) => "wallet_not_owned",
+                };
+                KristError::kromer(error_type, e)
+            }
+            KromerError::Validation(message) => {
+                KristError::Generic(generic::GenericError::InvalidParameter(message))
+            }
+            KromerError::NameListing(e) => KristError::kromer("name_listing", e),
+            KromerError::Hold(e) => KristError::kromer("hold", e),
+            KromerError::Invoice(e) => KristError::kromer("invoice", e),
+            KromerError::PendingTransfer(e) => KristError::kromer("pending_transfer", e),
+            KromerError::StandingOrder(e) => KristError::kromer("standing_order", e),
+            KromerError::Webhook(e) => KristError::kromer("webhook", e),
+            KromerError::WebSocket(_) | KromerError::Internal(_) | KromerError::IO(_) => {
+                KristError::kromer("internal_server_error", error)
             }
         }
     }
@@ -1,7 +1,7 @@
 use actix_web::{error, http::StatusCode, HttpResponse};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{krist_error_response, KristErrorExt};
 
 #[derive(Error, Debug)]
 pub enum AddressError {
@@ -10,6 +10,9 @@ pub enum AddressError {
 
     #[error("Authentication failed")]
     AuthFailed,
+
+    #[error("Address {0} is frozen")]
+    Frozen(String),
 }
 
 impl KristErrorExt for AddressError {
@@ -17,29 +20,21 @@ impl KristErrorExt for AddressError {
         match self {
             AddressError::NotFound(_) => "address_not_found",
             AddressError::AuthFailed => "auth_failed",
+            AddressError::Frozen(_) => "address_frozen",
         }
     }
 }
 
 impl error::ResponseError for AddressError {
     fn status_code(&self) -> StatusCode {
-        // TODO: Evaluate whether or not programs might break when returning the correct error code or not.
-        //       In Krist, responses are always error code 200 because of a long standing bug.
-        //       For some reason, that bug was never fixed and is just set there for forever, pretty stupid if you ask me.
         match self {
             AddressError::NotFound(_) => StatusCode::NOT_FOUND,
             AddressError::AuthFailed => StatusCode::UNAUTHORIZED,
+            AddressError::Frozen(_) => StatusCode::FORBIDDEN,
         }
     }
 
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        let message = KristErrorResponse {
-            ok: false,
-            error: self.error_type(),
-            message: self.to_string(),
-            info: None, // Unsure wheter or not this is right
-        };
-
-        HttpResponse::build(self.status_code()).json(message)
+        krist_error_response(self)
     }
 }
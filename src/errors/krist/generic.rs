@@ -1,7 +1,7 @@
-use actix_web::{error, HttpResponse};
+use actix_web::error;
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{krist_error_response, KristErrorExt};
 
 #[derive(Error, Debug)]
 pub enum GenericError {
@@ -20,14 +20,7 @@ impl error::ResponseError for GenericError {
     }
 
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        let message = KristErrorResponse {
-            ok: false,
-            error: self.error_type(),
-            message: self.to_string(),
-            info: None, // Unsure wheter or not this is right
-        };
-
-        HttpResponse::build(self.status_code()).json(message)
+        krist_error_response(self)
     }
 }
 
@@ -1,7 +1,7 @@
 use actix_web::{error, http::StatusCode, HttpResponse};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{krist_error_response, KristErrorExt};
 
 #[derive(Error, Debug)]
 pub enum NameError {
@@ -24,19 +24,12 @@ impl error::ResponseError for NameError {
             NameError::NameNotFound(_) => StatusCode::NOT_FOUND,
             NameError::NameTaken(_) => StatusCode::CONFLICT,
             NameError::NotNameOwner(_) => StatusCode::FORBIDDEN,
-            NameError::InsufficientBalance => StatusCode::IM_A_TEAPOT, // Really dont know what to put here instead of 418.
+            NameError::InsufficientBalance => StatusCode::FORBIDDEN, // Same as `TransactionError::InsufficientFunds`
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        let error = KristErrorResponse {
-            ok: false,
-            error: self.error_type(),
-            message: self.to_string(),
-            info: None,
-        };
-
-        HttpResponse::build(self.status_code()).json(error)
+        krist_error_response(self)
     }
 }
 
@@ -1,7 +1,7 @@
 use actix_web::{error, http::StatusCode, HttpResponse};
 use thiserror::Error;
 
-use super::{KristErrorExt, KristErrorResponse};
+use super::{krist_error_response, KristErrorExt};
 
 #[derive(Error, Debug)]
 pub enum TransactionError {
@@ -40,13 +40,6 @@ impl error::ResponseError for TransactionError {
     }
 
     fn error_response(&self) -> HttpResponse {
-        let error = KristErrorResponse {
-            ok: false,
-            error: self.error_type(),
-            message: self.to_string(),
-            info: None,
-        };
-
-        HttpResponse::build(self.status_code()).json(error)
+        krist_error_response(self)
     }
 }
@@ -1,7 +1,14 @@
+pub mod hold;
+pub mod invoice;
 pub mod krist;
 pub mod name;
+pub mod name_listing;
+pub mod pending_transfer;
+pub mod player;
+pub mod standing_order;
 pub mod transaction;
 pub mod wallet;
+pub mod webhook;
 pub mod websocket;
 
 use actix_web::{body::BoxBody, error, http::StatusCode, HttpResponse};
@@ -24,9 +31,30 @@ pub enum KromerError {
     #[error("Name error: {0}")]
     Name(#[from] name::NameError),
 
+    #[error("Name listing error: {0}")]
+    NameListing(#[from] name_listing::NameListingError),
+
+    #[error("Hold error: {0}")]
+    Hold(#[from] hold::HoldError),
+
+    #[error("Invoice error: {0}")]
+    Invoice(#[from] invoice::InvoiceError),
+
+    #[error("Pending transfer error: {0}")]
+    PendingTransfer(#[from] pending_transfer::PendingTransferError),
+
+    #[error("Player error: {0}")]
+    Player(#[from] player::PlayerError),
+
+    #[error("Standing order error: {0}")]
+    StandingOrder(#[from] standing_order::StandingOrderError),
+
     #[error("Transaction error: {0}")]
     Transaction(#[from] transaction::TransactionError),
 
+    #[error("Webhook error: {0}")]
+    Webhook(#[from] webhook::WebhookError),
+
     #[error("WebSocket error: {0}")]
     WebSocket(#[from] websocket::WebSocketError),
 
@@ -51,6 +79,13 @@ impl error::ResponseError for KromerError {
             KromerError::Wallet(e) => e.status_code(),
             KromerError::Transaction(e) => e.status_code(),
             KromerError::Name(e) => e.status_code(),
+            KromerError::NameListing(e) => e.status_code(),
+            KromerError::Player(e) => e.status_code(),
+            KromerError::PendingTransfer(e) => e.status_code(),
+            KromerError::Invoice(e) => e.status_code(),
+            KromerError::Hold(e) => e.status_code(),
+            KromerError::StandingOrder(e) => e.status_code(),
+            KromerError::Webhook(e) => e.status_code(),
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -62,6 +97,14 @@ impl error::ResponseError for KromerError {
                 KromerError::Database(..) => "database",
                 KromerError::Wallet(..) => "wallet",
                 KromerError::Transaction(..) => "transaction",
+                KromerError::Name(..) => "name",
+                KromerError::NameListing(..) => "name_listing",
+                KromerError::Player(..) => "player",
+                KromerError::PendingTransfer(..) => "pending_transfer",
+                KromerError::Invoice(..) => "invoice",
+                KromerError::Hold(..) => "hold",
+                KromerError::StandingOrder(..) => "standing_order",
+                KromerError::Webhook(..) => "webhook",
                 _ => "internal_server_error",
             },
             description: self.to_string(),
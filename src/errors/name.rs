@@ -7,6 +7,21 @@ pub enum NameError {
 
     #[error("Failed to transfer name")]
     FailedTransfer,
+
+    #[error("Name {0} is already taken")]
+    Taken(String),
+
+    #[error("Wallet is not the owner of name {0}")]
+    NotOwner(String),
+
+    #[error("Invalid name {0}")]
+    InvalidName(String),
+
+    #[error("Name {0} has expired and has to be renewed")]
+    Expired(String),
+
+    #[error("Names do not expire, so they can't be renewed")]
+    RentDisabled,
 }
 
 impl error::ResponseError for NameError {
@@ -14,6 +29,11 @@ impl error::ResponseError for NameError {
         match self {
             NameError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
             NameError::FailedTransfer => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            NameError::Taken(_) => actix_web::http::StatusCode::CONFLICT,
+            NameError::NotOwner(_) => actix_web::http::StatusCode::FORBIDDEN,
+            NameError::InvalidName(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            NameError::Expired(_) => actix_web::http::StatusCode::CONFLICT,
+            NameError::RentDisabled => actix_web::http::StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -0,0 +1,31 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NameListingError {
+    #[error("Listing not found")]
+    NotFound,
+
+    #[error("Listing is no longer active")]
+    NotActive,
+
+    #[error("Only the seller can cancel a listing")]
+    NotSeller,
+
+    #[error("Listing is reserved for another buyer")]
+    NotForBuyer,
+
+    #[error("Sellers can't buy their own listings")]
+    OwnListing,
+}
+
+impl error::ResponseError for NameListingError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            NameListingError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            NameListingError::NotActive => actix_web::http::StatusCode::CONFLICT,
+            NameListingError::NotSeller => actix_web::http::StatusCode::FORBIDDEN,
+            NameListingError::NotForBuyer => actix_web::http::StatusCode::FORBIDDEN,
+            NameListingError::OwnListing => actix_web::http::StatusCode::BAD_REQUEST,
+        }
+    }
+}
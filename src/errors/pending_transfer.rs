@@ -0,0 +1,27 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PendingTransferError {
+    #[error("Pending transfer not found")]
+    NotFound,
+
+    #[error("Pending transfer is no longer pending")]
+    NotPending,
+
+    #[error("Member already voted on this pending transfer")]
+    AlreadyVoted,
+
+    #[error("Only the proposer or an owner can cancel a pending transfer")]
+    NotAllowed,
+}
+
+impl error::ResponseError for PendingTransferError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            PendingTransferError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            PendingTransferError::NotPending => actix_web::http::StatusCode::CONFLICT,
+            PendingTransferError::AlreadyVoted => actix_web::http::StatusCode::CONFLICT,
+            PendingTransferError::NotAllowed => actix_web::http::StatusCode::FORBIDDEN,
+        }
+    }
+}
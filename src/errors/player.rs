@@ -0,0 +1,27 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlayerError {
+    #[error("Player not found")]
+    NotFound,
+
+    #[error("Player {0} has no wallet")]
+    NoWallet(String),
+
+    #[error("Player {0} has several wallets but none is set as primary")]
+    NoPrimaryWallet(String),
+
+    #[error("Wallet {0} is not owned by this player")]
+    WalletNotOwned(String),
+}
+
+impl error::ResponseError for PlayerError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            PlayerError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            PlayerError::NoWallet(_) => actix_web::http::StatusCode::NOT_FOUND,
+            PlayerError::NoPrimaryWallet(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            PlayerError::WalletNotOwned(_) => actix_web::http::StatusCode::FORBIDDEN,
+        }
+    }
+}
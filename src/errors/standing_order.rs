@@ -0,0 +1,35 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StandingOrderError {
+    #[error("Standing order not found")]
+    NotFound,
+
+    #[error("Invalid interval: {0}")]
+    InvalidInterval(String),
+
+    #[error("Standing order is not active")]
+    NotActive,
+
+    #[error("Standing order is not paused")]
+    NotPaused,
+
+    #[error("Standing order has already finished")]
+    Finished,
+
+    #[error("Not allowed to manage this standing order")]
+    NotAllowed,
+}
+
+impl error::ResponseError for StandingOrderError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            StandingOrderError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            StandingOrderError::InvalidInterval(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            StandingOrderError::NotActive => actix_web::http::StatusCode::CONFLICT,
+            StandingOrderError::NotPaused => actix_web::http::StatusCode::CONFLICT,
+            StandingOrderError::Finished => actix_web::http::StatusCode::CONFLICT,
+            StandingOrderError::NotAllowed => actix_web::http::StatusCode::FORBIDDEN,
+        }
+    }
+}
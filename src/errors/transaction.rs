@@ -13,6 +13,9 @@ pub enum TransactionError {
 
     #[error("Sender has insufficient funds")]
     InsufficientFunds,
+
+    #[error("Refund exceeds the transaction amount")]
+    RefundExceedsAmount,
 }
 
 impl error::ResponseError for TransactionError {
@@ -22,6 +25,7 @@ impl error::ResponseError for TransactionError {
             TransactionError::InvalidAmount => StatusCode::BAD_REQUEST,
             TransactionError::FailedCreate => StatusCode::INTERNAL_SERVER_ERROR,
             TransactionError::InsufficientFunds => StatusCode::BAD_REQUEST,
+            TransactionError::RefundExceedsAmount => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -13,6 +13,21 @@ pub enum WalletError {
 
     #[error("Invalid password")]
     InvalidPassword,
+
+    #[error("Not allowed to spend from this wallet")]
+    NotAllowed,
+
+    #[error("Amount exceeds the spending limit for this wallet")]
+    SpendingLimitExceeded,
+
+    #[error("A wallet must keep at least one owner")]
+    LastOwner,
+
+    #[error("Wallet {0} is frozen")]
+    Frozen(String),
+
+    #[error("Transfers of this amount from this wallet need to be approved by its members")]
+    ApprovalRequired,
 }
 
 impl error::ResponseError for WalletError {
@@ -22,6 +37,11 @@ impl error::ResponseError for WalletError {
             WalletError::FailedCreate => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
             WalletError::InvalidPassword => actix_web::http::StatusCode::BAD_REQUEST,
             WalletError::FailedTransfer => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            WalletError::NotAllowed => actix_web::http::StatusCode::FORBIDDEN,
+            WalletError::SpendingLimitExceeded => actix_web::http::StatusCode::FORBIDDEN,
+            WalletError::LastOwner => actix_web::http::StatusCode::BAD_REQUEST,
+            WalletError::ApprovalRequired => actix_web::http::StatusCode::FORBIDDEN,
+            WalletError::Frozen(_) => actix_web::http::StatusCode::FORBIDDEN,
         }
     }
 }
@@ -0,0 +1,31 @@
+use actix_web::error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Webhook not found")]
+    NotFound,
+
+    #[error("Webhook delivery not found")]
+    DeliveryNotFound,
+
+    #[error("Invalid webhook URL {0}")]
+    InvalidUrl(String),
+
+    #[error("Webhook URL {0} does not resolve to a public address")]
+    PrivateUrl(String),
+
+    #[error("A webhook needs at least one event")]
+    NoEvents,
+}
+
+impl error::ResponseError for WebhookError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            WebhookError::NotFound => actix_web::http::StatusCode::NOT_FOUND,
+            WebhookError::DeliveryNotFound => actix_web::http::StatusCode::NOT_FOUND,
+            WebhookError::InvalidUrl(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            WebhookError::PrivateUrl(_) => actix_web::http::StatusCode::BAD_REQUEST,
+            WebhookError::NoEvents => actix_web::http::StatusCode::BAD_REQUEST,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Fees charged on transfers. A fee is paid by the sender on top of the transferred amount and
+//! credited to the treasury wallet as a separate `fee` transaction linked to the transfer.
+//! Fees are disabled unless a treasury wallet is configured.
+
+use std::env;
+
+use once_cell::sync::Lazy;
+use rust_decimal::Decimal;
+use surrealdb::{engine::any::Any, Surreal};
+
+use crate::database::models::wallet::{self, Model as Wallet};
+use crate::errors::KromerError;
+
+/// Fees charged on transfers, configured through the environment.
+pub static FEE_SCHEDULE: Lazy<FeeSchedule> = Lazy::new(|| {
+    let decimal = |key: &str| {
+        env::var(key)
+            .ok()
+            .and_then(|value| value.parse::<Decimal>().ok())
+    };
+
+    FeeSchedule {
+        flat: decimal("TRANSFER_FEE_FLAT").unwrap_or_default(),
+        percent: decimal("TRANSFER_FEE_PERCENT").unwrap_or_default(),
+        min: decimal("TRANSFER_FEE_MIN").unwrap_or_default(),
+        max: decimal("TRANSFER_FEE_MAX"),
+        treasury: env::var("FEE_TREASURY_ADDRESS")
+            .ok()
+            .filter(|address| !address.trim().is_empty()),
+        exempt: env::var("TRANSFER_FEE_EXEMPT")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+});
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeeSchedule {
+    /// Charged on every transfer.
+    pub flat: Decimal,
+    /// Percentage of the transferred amount charged on top of the flat fee.
+    pub percent: Decimal,
+    pub min: Decimal,
+    pub max: Option<Decimal>,
+    /// Address of the wallet fees are credited to.
+    pub treasury: Option<String>,
+    /// Addresses that neither pay fees when sending nor cause them when receiving.
+    pub exempt: Vec<String>,
+}
+
+/// The fee a transfer would be charged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeQuote {
+    pub amount: Decimal,
+    pub treasury: Wallet,
+}
+
+impl FeeSchedule {
+    /// The fee for transferring `amount`, before exemptions.
+    pub fn fee_for(&self, amount: Decimal) -> Decimal {
+        let fee = self.flat + amount * self.percent / Decimal::ONE_HUNDRED;
+        let fee = fee.max(self.min);
+        let fee = self.max.map_or(fee, |max| fee.min(max));
+
+        fee.max(Decimal::ZERO).round_dp(2)
+    }
+
+    /// Whether transfers involving the address are free. System wallets and the treasury always are.
+    pub fn is_exempt(&self, address: &str) -> bool {
+        address == wallet::MINT_ADDRESS
+            || self.treasury.as_deref() == Some(address)
+            || self.exempt.iter().any(|exempt| exempt == address)
+    }
+
+    /// Quote the fee for a transfer between two wallets. Returns `None` if the transfer is free.
+    pub async fn quote(
+        &self,
+        db: &Surreal<Any>,
+        from: &Wallet,
+        to: &Wallet,
+        amount: Decimal,
+    ) -> Result<Option<FeeQuote>, KromerError> {
+        let Some(treasury) = &self.treasury else {
+            return Ok(None);
+        };
+        if self.is_exempt(&from.address) || self.is_exempt(&to.address) {
+            return Ok(None);
+        }
+
+        let fee = self.fee_for(amount);
+        if fee <= Decimal::ZERO {
+            return Ok(None);
+        }
+
+        let treasury = Wallet::get_by_address(db, treasury.clone())
+            .await?
+            .ok_or(KromerError::Internal("Fee treasury wallet does not exist"))?;
+
+        Ok(Some(FeeQuote {
+            amount: fee,
+            treasury,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_fee_for() {
+        let schedule = FeeSchedule {
+            flat: dec!(0.5),
+            percent: dec!(2),
+            min: dec!(1),
+            max: Some(dec!(10)),
+            treasury: Some("kromertreasury".into()),
+            exempt: vec!["kshop00000".into()],
+        };
+
+        // 0.5 + 2% of 10 is below the minimum
+        assert_eq!(schedule.fee_for(dec!(10)), dec!(1));
+        assert_eq!(schedule.fee_for(dec!(100)), dec!(2.5));
+        assert_eq!(schedule.fee_for(dec!(1000)), dec!(10));
+        assert_eq!(schedule.fee_for(dec!(33.33)), dec!(1.17));
+
+        assert!(schedule.is_exempt("kshop00000"));
+        assert!(schedule.is_exempt("kromertreasury"));
+        assert!(schedule.is_exempt(wallet::MINT_ADDRESS));
+        assert!(!schedule.is_exempt("kromernya1"));
+    }
+
+    #[test]
+    fn test_fee_disabled_by_default() {
+        assert_eq!(FeeSchedule::default().fee_for(dec!(100)), Decimal::ZERO);
+    }
+}
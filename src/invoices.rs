@@ -0,0 +1,72 @@
+//! Settlement of invoice payments, run after every transfer made through the API.
+
+use crate::database::models::invoice::Model as Invoice;
+use crate::database::models::transaction::Model as Transaction;
+use crate::database::models::wallet::Model as Wallet;
+use crate::models::websockets::WebSocketEventType;
+use crate::websockets::events;
+use crate::AppState;
+
+/// Settle the invoice a transfer was made for, if any, and notify the merchant and the payer.
+/// The transfer itself already succeeded, so failures are only logged. A refund that fails is
+/// recorded on the invoice and retried by the scheduler with [`retry_refunds`].
+pub async fn settle_payment(state: &AppState, transaction: &Transaction) {
+    let settlement = match Invoice::settle_payment(&state.db, transaction).await {
+        Ok(Some(settlement)) => settlement,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to settle invoice payment: {e}");
+            return;
+        }
+    };
+
+    let action = match (&settlement.refund, settlement.refund_failed) {
+        (Some(_), _) => "refunded",
+        (None, true) => "refund_failed",
+        (None, false) => "paid",
+    };
+    notify(state, &settlement.invoice, action, Some(transaction)).await;
+}
+
+/// Retry the refunds of invoice payments that failed earlier, notifying about the ones that succeed.
+pub async fn retry_refunds(state: &AppState) {
+    let refunded = match Invoice::retry_refunds(&state.db).await {
+        Ok(refunded) => refunded,
+        Err(e) => {
+            tracing::error!("Failed to retry invoice refunds: {e}");
+            return;
+        }
+    };
+
+    for (invoice, refund) in refunded {
+        notify(state, &invoice, "refunded", Some(&refund)).await;
+    }
+}
+
+/// Notify the merchant about a change to an invoice, and the payer if a payment is involved.
+pub async fn notify(
+    state: &AppState,
+    invoice: &Invoice,
+    action: &str,
+    payment: Option<&Transaction>,
+) {
+    let mut wallets = vec![invoice.wallet.to_raw()];
+    if let Some(payment) = payment {
+        wallets.push(payment.from.to_raw());
+    }
+
+    let mut addresses = Vec::with_capacity(wallets.len());
+    for wallet in wallets {
+        match Wallet::get(&state.db, wallet).await {
+            Ok(Some(wallet)) => addresses.push(wallet.address),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to get wallet to notify about invoice: {e}"),
+        }
+    }
+
+    let event = WebSocketEventType::Invoice {
+        action: action.to_string(),
+        invoice: Box::new(invoice.clone()),
+    };
+    events::send_to_addresses(state, &addresses, event).await;
+}
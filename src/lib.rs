@@ -1,14 +1,19 @@
 use std::sync::Arc;
 
+use database::stats::StatsCache;
 use surrealdb::{engine::any::Any, Surreal};
 use tokio::sync::Mutex;
 use websockets::{token_cache::TokenCache, ws_manager::WsDataManager, ws_server::WsServerHandle};
 
 pub mod database;
 pub mod errors;
+pub mod fees;
 pub mod guards;
+pub mod invoices;
 pub mod models;
 pub mod routes;
+pub mod scheduler;
+pub mod webhooks;
 pub mod websockets;
 
 #[derive(Debug)]
@@ -17,4 +22,5 @@ pub struct AppState {
     pub ws_server_handle: WsServerHandle,
     pub token_cache: Arc<Mutex<TokenCache>>,
     pub ws_manager: Arc<Mutex<WsDataManager>>,
+    pub stats_cache: Arc<Mutex<StatsCache>>,
 }
@@ -9,7 +9,8 @@ use kromer::websockets::ws_server::WsServer;
 use surrealdb::opt::auth::Root;
 
 use kromer::database::db::{ConnectionOptions, Database};
-use kromer::{errors::KromerError, routes, AppState};
+use kromer::database::stats::StatsCache;
+use kromer::{errors::KromerError, routes, scheduler, webhooks, AppState};
 use tokio::sync::Mutex;
 use tokio::{spawn, try_join};
 
@@ -51,13 +52,21 @@ async fn main() -> Result<(), KromerError> {
     let ws_server = spawn(ws_server.run());
     let token_cache = Arc::new(Mutex::new(TokenCache::new()));
     let ws_manager = Arc::new(Mutex::new(WsDataManager::default()));
+    let stats_cache = Arc::new(Mutex::new(StatsCache::new()));
+    webhooks::spawn_worker(db_arc.clone());
 
     let state = web::Data::new(AppState {
         db: db_arc,
         ws_server_handle,
         token_cache,
         ws_manager,
+        stats_cache,
     });
+    scheduler::spawn_worker(state.clone().into_inner());
+    scheduler::spawn_refund_retries(state.clone().into_inner());
+    scheduler::spawn_name_sweep(state.clone().into_inner());
+    scheduler::spawn_pending_transfer_expiry(state.clone().into_inner());
+    scheduler::spawn_balance_snapshots(state.clone().into_inner());
 
     let http_server = HttpServer::new(move || {
         App::new()
@@ -20,6 +20,9 @@ pub struct AddressResponse {
 pub struct AddressJson {
     pub address: String,
     pub balance: Decimal,
+    /// The balance minus funds reserved by holds.
+    #[serde(default)]
+    pub available: Decimal,
     #[serde(rename = "totalin")]
     pub total_in: Decimal,
     #[serde(rename = "totalout")]
@@ -39,6 +42,7 @@ pub struct VerifyResponse {
 impl From<wallet::Model> for AddressJson {
     fn from(wallet: wallet::Model) -> Self {
         Self {
+            available: wallet.available_balance(),
             address: wallet.address,
             balance: wallet.balance,
             total_in: wallet.total_in,
@@ -74,6 +78,7 @@ mod tests {
             address: AddressJson {
                 address: "kre3w0i79j".to_owned(),
                 balance: rust_decimal_macros::dec!(86945.0),
+                available: rust_decimal_macros::dec!(86945.0),
                 total_in: rust_decimal_macros::dec!(123364.0),
                 total_out: rust_decimal_macros::dec!(38292.0),
                 first_seen: "2015-03-13T12:55:18.000Z".to_owned(),
@@ -81,7 +86,7 @@ mod tests {
             },
         };
         let response_str = serde_json::to_string(&response).expect("Failed to serialize");
-        let response_str_test = r#"{"ok":true,"address":{"address":"kre3w0i79j","balance":86945,"totalin":123364,"totalout":38292,"firstseen":"2015-03-13T12:55:18.000Z"}}"#;
+        let response_str_test = r#"{"ok":true,"address":{"address":"kre3w0i79j","balance":86945,"available":86945,"totalin":123364,"totalout":38292,"firstseen":"2015-03-13T12:55:18.000Z"}}"#;
 
         assert_eq!(response_str, response_str_test);
     }
@@ -4,6 +4,7 @@ pub mod blocks;
 pub mod error;
 pub mod motd;
 pub mod names;
+pub mod stats;
 pub mod transactions;
 pub mod webserver;
 pub mod websockets;
@@ -26,3 +27,19 @@ where
         StringOrInt::Number(i) => Ok(i.to_string()),
     }
 }
+
+/// Deserialize a comma separated string into a list, e.g. `a,b,c`. Empty entries are skipped.
+pub fn deserialize_comma_separated<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+
+    Ok(value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
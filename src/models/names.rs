@@ -32,11 +32,17 @@ pub struct NameJson {
     pub registered: Option<String>,
     pub updated: Option<String>,
     pub transfered: Option<String>,
+    /// Whole days left to renew the name before it is released, 0 if it hasn't expired.
     pub unpaid: i64,
+    /// When the name has to be renewed by, if names expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
 }
 
 impl From<name::Model> for NameJson {
     fn from(name: name::Model) -> Self {
+        let unpaid = name.unpaid();
+
         Self {
             name: name.name,
             owner: Some(name.owner.to_raw()), // TODO: Use correct values
@@ -44,7 +50,8 @@ impl From<name::Model> for NameJson {
             registered: Some(name.registered.to_rfc3339()),
             updated: None,
             transfered: None, // TODO: Populate this
-            unpaid: 0,
+            unpaid,
+            expires: name.expires_at.map(|expires_at| expires_at.to_rfc3339()),
         }
     }
 }
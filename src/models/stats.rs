@@ -0,0 +1,25 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::database::models::transaction::{TopMover, TransactionActivity};
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct MoneySupplyResponse {
+    pub ok: bool,
+    /// The amount of Krist currently in circulation.
+    pub money_supply: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct EconomyStats {
+    /// The sum of all wallet balances.
+    pub supply: Decimal,
+    pub wallets: usize,
+    pub names: usize,
+    pub last_day: TransactionActivity,
+    pub last_week: TransactionActivity,
+    /// Wallets that moved the most money in the last day.
+    pub top_movers: Vec<TopMover>,
+    /// The time these statistics were computed, as an ISO-8601 string.
+    pub generated_at: String,
+}
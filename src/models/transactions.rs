@@ -1,8 +1,9 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use surrealdb::sql::Id;
 
 use crate::database::models::transaction;
-use transaction::TransactionNameData;
+use transaction::{CommonMeta, TransactionNameData};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct TransactionListResponse {
@@ -45,6 +46,9 @@ pub struct TransactionJson {
     pub metadata: Option<String>,
     pub sent_metaname: Option<String>,
     pub sent_name: Option<String>,
+    /// The metadata parsed according to CommonMeta.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parsed_metadata: Option<CommonMeta>,
     #[serde(rename = "type")]
     pub transaction_type: TransactionType,
 }
@@ -59,11 +63,15 @@ pub enum TransactionType {
     NameARecord,
     NameTransfer,
     Transfer,
+    Mint,
+    Burn,
+    Fee,
 }
 
 impl From<transaction::Model> for TransactionJson {
     fn from(transaction: transaction::Model) -> Self {
         let name_data = TransactionNameData::parse_opt_ref(&transaction.metadata);
+        let parsed_metadata = transaction.metadata.as_deref().map(CommonMeta::parse);
 
         Self {
             id: 0,                                    // We dont do incremental IDs, do we give a shit?
@@ -71,10 +79,14 @@ impl From<transaction::Model> for TransactionJson {
             to: Some(transaction.to.to_string()),     // TODO: use address actual address instead.
             value: transaction.amount,
             time: transaction.timestamp.to_raw(),
-            name: None, // TODO: Populate this later, maybe with a separate function.
+            name: transaction.name.map(|name| match name.id {
+                Id::String(name) => name,
+                id => id.to_raw(),
+            }),
             metadata: transaction.metadata,
             sent_metaname: name_data.meta,
             sent_name: name_data.name,
+            parsed_metadata,
             transaction_type: transaction.transaction_type,
         }
     }
@@ -89,6 +101,9 @@ impl From<TransactionType> for &str {
             TransactionType::NameARecord => "name_a_record",
             TransactionType::NameTransfer => "name_transfer",
             TransactionType::Transfer => "transfer",
+            TransactionType::Mint => "mint",
+            TransactionType::Burn => "burn",
+            TransactionType::Fee => "fee",
         }
     }
 }
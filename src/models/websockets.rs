@@ -71,6 +71,7 @@ pub enum WebSocketMessageType {
         #[serde(rename = "privatekey")]
         private_key: Option<String>,
         to: Option<String>,
+        to_player: Option<String>,
         amount: Option<Decimal>,
         metadata: Option<String>,
         #[serde(rename = "requestId")]
@@ -140,6 +141,23 @@ pub enum WebSocketEventType {
     },
     Name {
         name: super::names::NameJson,
+        /// What happened to the name if it isn't a registration or transfer, e.g. `expired` or `listed`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        action: Option<String>,
+        /// The listing of the name, for marketplace actions.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        listing: Option<Box<crate::database::models::name_listing::Model>>,
+    },
+    #[serde(rename = "pending_transfer")]
+    PendingTransfer {
+        /// What happened to the pending transfer, e.g. `proposed` or `executed`.
+        action: String,
+        pending_transfer: Box<crate::database::models::pending_transfer::Model>,
+    },
+    Invoice {
+        /// What happened to the invoice, e.g. `paid` or `refunded`.
+        action: String,
+        invoice: Box<crate::database::models::invoice::Model>,
     },
 }
 
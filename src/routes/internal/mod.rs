@@ -1,7 +1,11 @@
+pub mod player;
 pub mod wallet;
+pub mod webhook;
 
 use actix_web::web;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.configure(wallet::config);
+    cfg.configure(player::config);
+    cfg.configure(webhook::config);
 }
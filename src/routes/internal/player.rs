@@ -0,0 +1,99 @@
+use actix_web::{get, post, web, HttpResponse};
+use rust_decimal::Decimal;
+use serde_json::json;
+use surrealdb::{engine::any::Any, sql::Thing, Surreal};
+
+use crate::database::models::player::Model as Player;
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::player::PlayerError;
+use crate::errors::wallet::WalletError;
+use crate::{errors::KromerError, AppState};
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct CreateWalletReq {
+    /// Make the new wallet the primary wallet of the player.
+    #[serde(default)]
+    pub primary: bool,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct SetPrimaryReq {
+    pub address: String,
+}
+
+#[get("/{uuid}/wallets")]
+async fn player_wallets(
+    state: web::Data<AppState>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let player = player_id(db, &uuid.into_inner()).await?;
+
+    let wallets = Wallet::get_by_player(db, player).await?;
+
+    Ok(HttpResponse::Ok().json(wallets))
+}
+
+#[post("/{uuid}/wallets")]
+async fn player_create_wallet(
+    state: web::Data<AppState>,
+    uuid: web::Path<String>,
+    data: web::Json<CreateWalletReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+    let player = player_id(db, &uuid.into_inner()).await?;
+
+    let wallet = Wallet::create_for_player(db, player, data.primary, Decimal::ZERO, None).await?;
+
+    let resp = json!({
+        "password": wallet.password,
+        "address": wallet.address,
+        "primary": data.primary
+    });
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[post("/{uuid}/primary")]
+async fn player_set_primary(
+    state: web::Data<AppState>,
+    uuid: web::Path<String>,
+    data: web::Json<SetPrimaryReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+    let player = player_id(db, &uuid.into_inner()).await?;
+
+    let wallet = Wallet::get_by_address(db, data.address)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    Wallet::set_primary(db, player, &wallet).await?;
+
+    let resp = json!({
+        "ok": true,
+        "address": wallet.address
+    });
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Get the id of a player from their Minecraft UUID, erroring if they don't exist.
+pub(super) async fn player_id(db: &Surreal<Any>, uuid: &str) -> Result<Thing, KromerError> {
+    let player = Player::get_partial(db, uuid)
+        .await?
+        .ok_or(KromerError::Player(PlayerError::NotFound))?;
+
+    player
+        .id
+        .ok_or(KromerError::Internal("Player is missing its id"))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/player")
+            .service(player_wallets)
+            .service(player_create_wallet)
+            .service(player_set_primary),
+    );
+}
@@ -1,14 +1,33 @@
-use actix_web::{post, web, HttpResponse};
+use std::env;
+
+use actix_web::{delete, get, post, web, HttpResponse};
+use once_cell::sync::Lazy;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde_json::json;
+use surrealdb::sql::{Id, Thing};
 
+use super::player::player_id;
 use crate::database::models::player::Model as Player;
-use crate::database::models::wallet::Model as Wallet;
+use crate::database::models::transaction::{BatchPayout, BatchSource, Model as Transaction};
+use crate::database::models::wallet::{Model as Wallet, MultisigPolicy, OwnedWallet, WalletRole};
+use crate::errors::player::PlayerError;
 use crate::errors::transaction::TransactionError;
 use crate::errors::wallet::WalletError;
+use crate::websockets::{events, ws_manager};
 use crate::{errors::KromerError, AppState};
 
+/// Most payouts a single batch can make.
+const MAX_BATCH_PAYOUTS: usize = 1000;
+
+/// Balance given to new wallets, recorded as a `mint` transaction.
+static STARTING_BALANCE: Lazy<Decimal> = Lazy::new(|| {
+    env::var("STARTING_BALANCE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(dec!(100))
+});
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 struct MinecraftUser {
     pub name: String,
@@ -19,18 +38,70 @@ struct MinecraftUser {
 struct GiveMoneyReq {
     pub address: String,
     pub amount: Decimal,
+    /// Why the money was given, recorded in the transaction metadata.
+    pub reason: Option<String>,
+    /// Identity of the admin giving the money, recorded in the transaction metadata.
+    pub admin: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
-struct Guh {
-    pub name: String,
+struct TakeMoneyReq {
+    pub address: String,
+    pub amount: Decimal,
+    /// Take whatever the wallet has if it can't cover the full amount, instead of failing.
+    #[serde(default)]
+    pub force: bool,
+    /// Why the money was taken, recorded in the transaction metadata.
+    pub reason: Option<String>,
+    /// Identity of the admin taking the money, recorded in the transaction metadata.
+    pub admin: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
-struct WalletCreateResp {
-    pub address: String,
-    pub password: String,
-    pub wallet: Wallet,
+struct BatchReq {
+    /// Address of the wallet funding the payouts, new money is minted if not given.
+    pub from: Option<String>,
+    pub payouts: Vec<BatchPayoutReq>,
+    /// Why the money was given, recorded in the metadata of payouts without their own.
+    pub reason: Option<String>,
+    /// Identity of the admin giving the money, recorded in the metadata of payouts without their own.
+    pub admin: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct BatchPayoutReq {
+    /// Address of the recipient.
+    pub address: Option<String>,
+    /// Minecraft UUID of the player whose primary wallet is paid, instead of `address`.
+    pub mc_uuid: Option<String>,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct SetMemberReq {
+    pub mc_uuid: String,
+    #[serde(default)]
+    pub role: WalletRole,
+    /// Maximum amount the member can send within the spending limit window, only used for spenders.
+    pub spending_limit: Option<Decimal>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct RecoverReq {
+    pub mc_uuid: String,
+    /// Wallet to recover, defaults to the primary wallet of the player.
+    pub address: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct FreezeReq {
+    pub reason: String,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct Guh {
+    pub name: String,
 }
 
 #[post("/create")]
@@ -38,32 +109,94 @@ async fn wallet_create(
     state: web::Data<AppState>,
     user: web::Json<MinecraftUser>,
 ) -> Result<HttpResponse, KromerError> {
-    // fn::create_wallet_with_user(username)
     let db = &state.db;
     let user = user.into_inner();
 
-    let player: Option<Player> = db
-        .create(("player", user.mc_uuid))
-        .content(Guh { name: user.name })
-        .await?;
-    let player = player.ok_or_else(|| KromerError::Internal("Unable to get created player"))?;
-
-    let mut resp = db.query("RETURN fn::create_wallet(100);").await?;
-    let wallet: Option<WalletCreateResp> = resp.take(0)?;
-    let wallet = wallet.ok_or_else(|| KromerError::Internal("Unable to get created wallet"))?;
-
-    let q = "RELATE $player->owns->$wallet";
-    let resp = db
-        .query(q)
-        .bind(("player", player.id.unwrap()))
-        .bind(("wallet", wallet.wallet.id.unwrap()))
-        .await?;
-    tracing::debug!("Got response: {resp:?}");
-
-    // Yeah i dont like this either
+    let player = match Player::get_partial(db, &user.mc_uuid).await? {
+        Some(player) if player.name == user.name => player,
+        Some(player) => {
+            let id = player
+                .id
+                .ok_or(KromerError::Internal("Player is missing its id"))?;
+            Player::release_name(db, id.clone(), user.name.clone()).await?;
+            Player::set_name(db, id, user.name)
+                .await?
+                .ok_or(KromerError::Internal("Unable to get updated player"))?
+        }
+        None => {
+            let id = Thing::from(("player", Id::from(user.mc_uuid.as_str())));
+            Player::release_name(db, id, user.name.clone()).await?;
+            let player: Option<Player> = db
+                .create(("player", user.mc_uuid))
+                .content(Guh { name: user.name })
+                .await?;
+            player.ok_or_else(|| KromerError::Internal("Unable to get created player"))?
+        }
+    };
+    let player_id = player
+        .id
+        .ok_or(KromerError::Internal("Player is missing its id"))?;
+
+    let metadata = admin_metadata(Some("Starting balance"), None);
+    let created =
+        Wallet::create_first_for_player(db, player_id.clone(), *STARTING_BALANCE, metadata).await?;
+
+    // Returning players get their primary wallet back, the password can't be recovered from the hash.
+    let Some(wallet) = created else {
+        let existing = Wallet::get_by_player(db, player_id).await?;
+        let wallet = OwnedWallet::primary(existing, player.name)?;
+        let resp = json!({
+            "address": wallet.address,
+            "existing": true
+        });
+
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+
     let resp = json!({
         "password": wallet.password,
-        "address": wallet.address
+        "address": wallet.address,
+        "existing": false
+    });
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[post("/recover")]
+async fn wallet_recover(
+    state: web::Data<AppState>,
+    data: web::Json<RecoverReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+
+    let player = Player::get_partial(db, &data.mc_uuid)
+        .await?
+        .ok_or(KromerError::Player(PlayerError::NotFound))?;
+    let player_id = player
+        .id
+        .ok_or(KromerError::Internal("Player is missing its id"))?;
+
+    // Only wallets the player owns can be recovered, not ones they are merely a member of.
+    let owned = Wallet::get_by_player(db, player_id).await?;
+    let wallet = match data.address {
+        Some(address) => owned
+            .into_iter()
+            .find(|owned| owned.wallet.address == address && owned.role == WalletRole::Owner)
+            .map(|owned| owned.wallet)
+            .ok_or(KromerError::Player(PlayerError::WalletNotOwned(address)))?,
+        None => OwnedWallet::primary(owned, player.name)?,
+    };
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let password = Wallet::rotate_key(db, id).await?;
+    ws_manager::invalidate_address(&state, &wallet.address).await;
+
+    let resp = json!({
+        "address": wallet.address,
+        "password": password
     });
 
     Ok(HttpResponse::Ok().json(resp))
@@ -77,31 +210,372 @@ async fn wallet_give_money(
     let db = &state.db;
     let data = data.into_inner();
 
-    if data.amount < dec!(0.0) {
+    if data.amount <= dec!(0.0) {
+        return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+    }
+
+    let wallet = Wallet::get_by_address(db, data.address)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+    let metadata = admin_metadata(data.reason.as_deref(), data.admin.as_deref());
+    let transaction = Transaction::mint(db, &wallet, data.amount, metadata).await?;
+    events::send_transaction(&state, &transaction).await;
+
+    let resp = json!({
+        "ok": true,
+        "transaction": transaction
+    });
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[post("/take-money")]
+async fn wallet_take_money(
+    state: web::Data<AppState>,
+    data: web::Json<TakeMoneyReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+
+    if data.amount <= dec!(0.0) {
         return Err(KromerError::Transaction(TransactionError::InvalidAmount));
     }
 
+    let wallet = Wallet::get_by_address(db, data.address.clone())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+    let metadata = admin_metadata(data.reason.as_deref(), data.admin.as_deref());
+    let transaction = Transaction::burn(db, &wallet, data.amount, data.force, metadata).await?;
+    events::send_transaction(&state, &transaction).await;
+
     let wallet = Wallet::get_by_address(db, data.address)
         .await?
         .ok_or(KromerError::Wallet(WalletError::NotFound))?;
-    let q = "UPDATE $wallet SET balance += $amount";
-    let _ = db
-        .query(q)
-        .bind(("wallet", wallet.id.unwrap()))
-        .bind(("amount", data.amount))
-        .await?;
 
     let resp = json!({
-        "ok": true
+        "ok": true,
+        "balance": wallet.balance,
+        "transaction": transaction
+    });
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+#[post("/batch")]
+async fn wallet_batch(
+    state: web::Data<AppState>,
+    data: web::Json<BatchReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+
+    if data.payouts.is_empty() || data.payouts.len() > MAX_BATCH_PAYOUTS {
+        return Err(KromerError::Validation(format!(
+            "A batch needs between 1 and {MAX_BATCH_PAYOUTS} payouts"
+        )));
+    }
+
+    let from = match data.from {
+        Some(address) => Some(
+            Wallet::get_by_address(db, address)
+                .await?
+                .ok_or(KromerError::Wallet(WalletError::NotFound))?,
+        ),
+        None => None,
+    };
+    let default_metadata = admin_metadata(data.reason.as_deref(), data.admin.as_deref());
+
+    // Check every payout before making any, so all problems are reported at once.
+    let mut payouts = Vec::with_capacity(data.payouts.len());
+    let mut errors = Vec::new();
+    for (index, payout) in data.payouts.into_iter().enumerate() {
+        match batch_payout(&state, payout, &default_metadata).await {
+            Ok(payout) => payouts.push(payout),
+            Err(e) => errors.push(json!({
+                "index": index,
+                "ok": false,
+                "error": e.to_string(),
+            })),
+        }
+    }
+    if !errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "ok": false,
+            "error": "Some payouts are invalid, none were made",
+            "results": errors,
+        })));
+    }
+
+    let source = match &from {
+        Some(wallet) => BatchSource::Wallet(wallet),
+        None => BatchSource::Mint,
+    };
+    let addresses: Vec<String> = payouts
+        .iter()
+        .map(|payout| payout.to.address.clone())
+        .collect();
+    let transactions = Transaction::batch(db, source, payouts).await?;
+
+    for transaction in &transactions {
+        events::send_transaction(&state, transaction).await;
+    }
+
+    let total: Decimal = transactions
+        .iter()
+        .map(|transaction| transaction.amount)
+        .sum();
+    let results: Vec<_> = transactions
+        .into_iter()
+        .zip(addresses)
+        .enumerate()
+        .map(|(index, (transaction, address))| {
+            json!({
+                "index": index,
+                "ok": true,
+                "address": address,
+                "transaction": transaction,
+            })
+        })
+        .collect();
+
+    let resp = json!({
+        "ok": true,
+        "total": total,
+        "results": results,
     });
 
     Ok(HttpResponse::Ok().json(resp))
 }
 
+/// Resolve the recipient of a payout in a batch and check it can be paid.
+async fn batch_payout(
+    state: &AppState,
+    payout: BatchPayoutReq,
+    default_metadata: &Option<String>,
+) -> Result<BatchPayout, KromerError> {
+    let db = &state.db;
+
+    if payout.amount <= dec!(0.0) {
+        return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+    }
+
+    let to = match (payout.address, payout.mc_uuid) {
+        (Some(address), None) => Wallet::get_by_address(db, address)
+            .await?
+            .ok_or(KromerError::Wallet(WalletError::NotFound))?,
+        (None, Some(mc_uuid)) => Wallet::get_primary_for_player_uuid(db, &mc_uuid).await?,
+        (Some(_), Some(_)) => {
+            return Err(KromerError::Validation(
+                "Only one of address and mc_uuid can be given".into(),
+            ))
+        }
+        (None, None) => return Err(KromerError::Validation("Missing recipient".into())),
+    };
+    to.ensure_can_receive()?;
+
+    Ok(BatchPayout {
+        to,
+        amount: payout.amount,
+        metadata: payout.metadata.or_else(|| default_metadata.clone()),
+    })
+}
+
+#[post("/{address}/freeze")]
+async fn wallet_freeze(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    data: web::Json<FreezeReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+
+    if data.reason.trim().is_empty() {
+        return Err(KromerError::Validation("Missing freeze reason".into()));
+    }
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let wallet = Wallet::set_frozen(db, id, Some(data.reason))
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(wallet))
+}
+
+#[post("/{address}/unfreeze")]
+async fn wallet_unfreeze(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let wallet = Wallet::set_frozen(db, id, None)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(wallet))
+}
+
+#[post("/{address}/multisig")]
+async fn wallet_set_multisig(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    policy: web::Json<MultisigPolicy>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let policy = policy.into_inner();
+
+    if policy.threshold <= dec!(0.0) {
+        return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+    }
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    // A policy that can never be met would lock large transfers out of the wallet for good.
+    let voters = Wallet::get_members(db, id.clone())
+        .await?
+        .iter()
+        .filter(|member| member.role != WalletRole::Viewer)
+        .count();
+    if policy.approvals == 0 || policy.approvals as usize > voters {
+        return Err(KromerError::Validation(format!(
+            "Approvals must be between 1 and the {voters} members that can vote"
+        )));
+    }
+
+    let wallet = Wallet::set_multisig(db, id, Some(policy))
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(wallet.multisig))
+}
+
+#[delete("/{address}/multisig")]
+async fn wallet_remove_multisig(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    Wallet::set_multisig(db, id, None).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "ok": true })))
+}
+
+#[get("/{address}/members")]
+async fn wallet_members(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let members = Wallet::get_members(db, id).await?;
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[post("/{address}/members")]
+async fn wallet_set_member(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    data: web::Json<SetMemberReq>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let data = data.into_inner();
+
+    if data.spending_limit.is_some_and(|limit| limit < dec!(0.0)) {
+        return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+    }
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let player = player_id(db, &data.mc_uuid).await?;
+
+    let members = Wallet::set_member(db, &wallet, player, data.role, data.spending_limit).await?;
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[delete("/{address}/members/{mc_uuid}")]
+async fn wallet_remove_member(
+    state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let (address, mc_uuid) = path.into_inner();
+
+    let wallet = Wallet::get_by_address(db, address)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let player = player_id(db, &mc_uuid).await?;
+
+    let members = Wallet::remove_member(db, &wallet, player).await?;
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+/// Build CommonMeta style metadata for a mint or burn transaction, e.g. `reason=Event prize;admin=Steve`.
+fn admin_metadata(reason: Option<&str>, admin: Option<&str>) -> Option<String> {
+    let entries: Vec<String> = [("reason", reason), ("admin", admin)]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            // `;` separates entries, so it can't be part of a value
+            let value = value?.replace(';', ",");
+            (!value.trim().is_empty()).then(|| format!("{key}={}", value.trim()))
+        })
+        .collect();
+
+    (!entries.is_empty()).then(|| entries.join(";"))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/wallet")
             .service(wallet_create)
-            .service(wallet_give_money),
+            .service(wallet_recover)
+            .service(wallet_give_money)
+            .service(wallet_take_money)
+            .service(wallet_batch)
+            .service(wallet_freeze)
+            .service(wallet_unfreeze)
+            .service(wallet_set_multisig)
+            .service(wallet_remove_multisig)
+            .service(wallet_members)
+            .service(wallet_set_member)
+            .service(wallet_remove_member),
     );
 }
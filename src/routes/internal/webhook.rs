@@ -0,0 +1,87 @@
+use actix_web::{get, post, web, HttpResponse};
+use surrealdb::sql::{Id, Thing};
+
+use crate::database::models::wallet::Model as Wallet;
+use crate::database::models::webhook::{Delivery, DeliveryStatus, Model as Webhook};
+use crate::errors::wallet::WalletError;
+use crate::errors::webhook::WebhookError;
+use crate::routes::PaginationParams;
+use crate::{errors::KromerError, AppState};
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct DeliveryQuery {
+    /// Only include deliveries of this webhook.
+    pub webhook: Option<String>,
+    /// Only include deliveries with this status, `dead` gives the dead-letter list.
+    pub status: Option<DeliveryStatus>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+#[get("/wallet/{address}")]
+async fn webhook_list(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let webhooks = Webhook::get_by_wallet(db, id).await?;
+
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+#[get("/deliveries")]
+async fn delivery_list(
+    state: web::Data<AppState>,
+    query: web::Query<DeliveryQuery>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let query = query.into_inner();
+
+    let webhook = query
+        .webhook
+        .map(|id| Thing::from(("webhook", Id::from(id))));
+    let pagination = PaginationParams {
+        limit: query.limit,
+        offset: query.offset,
+    };
+
+    let deliveries = Delivery::list(db, webhook, query.status, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+#[post("/deliveries/{id}/replay")]
+async fn delivery_replay(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let delivery = Delivery::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::Webhook(WebhookError::DeliveryNotFound))?;
+    let id = delivery
+        .id
+        .ok_or(KromerError::Internal("Delivery is missing its id"))?;
+
+    let delivery = Delivery::replay(db, id).await?;
+
+    Ok(HttpResponse::Ok().json(delivery))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/webhook")
+            .service(webhook_list)
+            .service(delivery_list)
+            .service(delivery_replay),
+    );
+}
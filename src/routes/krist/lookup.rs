@@ -0,0 +1,127 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::database::models::transaction::{
+    Model as Transaction, TransactionFilter, TransactionOrder, TransactionSort,
+};
+use crate::errors::krist::{generic::GenericError, KristError};
+use crate::models::transactions::{TransactionJson, TransactionListResponse};
+use crate::models::webserver::lookup::{LookupQuery, TransactionLookupFields};
+use crate::{routes::PaginationParams, AppState};
+
+#[get("/transactions")]
+async fn lookup_transactions(
+    state: web::Data<AppState>,
+    query: web::Query<LookupQuery>,
+    filter: web::Query<TransactionFilter>,
+) -> Result<HttpResponse, KristError> {
+    let pagination = pagination_from_lookup(&query)?;
+    let order = transaction_order_from_lookup(&query)?;
+    let filter = filter.into_inner();
+
+    transaction_list_response(&state, &filter, &order, &pagination).await
+}
+
+#[get("/transactions/{addresses}")]
+async fn lookup_transactions_by_addresses(
+    state: web::Data<AppState>,
+    addresses: web::Path<String>,
+    query: web::Query<LookupQuery>,
+    filter: web::Query<TransactionFilter>,
+) -> Result<HttpResponse, KristError> {
+    let pagination = pagination_from_lookup(&query)?;
+    let order = transaction_order_from_lookup(&query)?;
+    let mut filter = filter.into_inner();
+    filter.addresses = addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    transaction_list_response(&state, &filter, &order, &pagination).await
+}
+
+async fn transaction_list_response(
+    state: &AppState,
+    filter: &TransactionFilter,
+    order: &TransactionOrder,
+    pagination: &PaginationParams,
+) -> Result<HttpResponse, KristError> {
+    let db = &state.db;
+
+    let total = Transaction::count_filtered(db, filter).await?;
+    let transactions: Vec<TransactionJson> = Transaction::filtered(db, filter, order, pagination)
+        .await?
+        .into_iter()
+        .map(|trans| trans.into())
+        .collect();
+
+    Ok(HttpResponse::Ok().json(TransactionListResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    }))
+}
+
+/// Lookup routes take `limit` and `offset` as strings, like Krist does.
+fn pagination_from_lookup(query: &LookupQuery) -> Result<PaginationParams, GenericError> {
+    let parse = |value: &Option<String>, parameter: &str| {
+        value
+            .as_deref()
+            .map(str::parse::<u64>)
+            .transpose()
+            .map_err(|_| GenericError::InvalidParameter(parameter.to_owned()))
+    };
+
+    Ok(PaginationParams {
+        limit: parse(&query.limit, "limit")?,
+        offset: parse(&query.offset, "offset")?,
+    })
+}
+
+/// Lookup routes take `orderBy` and `order` like Krist does, sorting by time in descending order
+/// if they aren't given. Transactions can't be sorted by the name they were sent to.
+fn transaction_order_from_lookup(query: &LookupQuery) -> Result<TransactionOrder, GenericError> {
+    let sort = match query.order_by.as_deref() {
+        None => TransactionSort::Time,
+        Some(order_by) => {
+            let field: TransactionLookupFields =
+                serde_json::from_value(serde_json::Value::String(order_by.to_owned()))
+                    .map_err(|_| GenericError::InvalidParameter("orderBy".to_owned()))?;
+
+            match field {
+                TransactionLookupFields::Id | TransactionLookupFields::Time => {
+                    TransactionSort::Time
+                }
+                TransactionLookupFields::Value => TransactionSort::Amount,
+                TransactionLookupFields::From => TransactionSort::From,
+                TransactionLookupFields::To => TransactionSort::To,
+                TransactionLookupFields::SentName | TransactionLookupFields::SentMetaname => {
+                    return Err(GenericError::InvalidParameter("orderBy".to_owned()))
+                }
+            }
+        }
+    };
+
+    let ascending = match query
+        .order
+        .as_deref()
+        .map(str::to_ascii_uppercase)
+        .as_deref()
+    {
+        None | Some("DESC") => false,
+        Some("ASC") => true,
+        Some(_) => return Err(GenericError::InvalidParameter("order".to_owned())),
+    };
+
+    Ok(TransactionOrder { sort, ascending })
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/lookup")
+            .service(lookup_transactions)
+            .service(lookup_transactions_by_addresses),
+    );
+}
@@ -1,4 +1,6 @@
+mod lookup;
 mod names;
+mod supply;
 mod transactions;
 mod wallet;
 mod ws;
@@ -10,6 +12,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.configure(transactions::config);
     cfg.configure(ws::config);
     cfg.configure(names::config);
+    cfg.configure(lookup::config);
+    cfg.configure(supply::config);
     // cfg.configure(transaction::config);
     // cfg.configure(name::config);
 }
@@ -1,10 +1,24 @@
-use actix_web::{get, web, HttpResponse};
+use actix_web::{get, post, web, HttpResponse};
+use rust_decimal::Decimal;
 
-use crate::database::models::name::Model as Name;
-use crate::errors::krist::{name::NameError, KristError};
-use crate::models::names::{NameJson, NameListResponse, NameResponse};
+use crate::database::models::name::{Model as Name, NAME_COST};
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::krist::{address::AddressError, name::NameError, KristError};
+use crate::models::names::{DetailedUnpaidResponseRow, NameJson, NameListResponse, NameResponse};
+use crate::websockets::events;
 use crate::{routes::PaginationParams, AppState};
 
+#[derive(Debug, serde::Deserialize)]
+struct NameRegisterDetails {
+    privatekey: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NameTransferDetails {
+    privatekey: String,
+    address: String,
+}
+
 #[get("")]
 async fn name_list(
     state: web::Data<AppState>,
@@ -28,6 +42,30 @@ async fn name_list(
     Ok(HttpResponse::Ok().json(response))
 }
 
+#[get("/unpaid")]
+async fn name_unpaid(state: web::Data<AppState>) -> Result<HttpResponse, KristError> {
+    let db = &state.db;
+
+    let names = Name::get_unpaid(db).await?;
+
+    // Group the names by the days left to renew them
+    let mut rows: Vec<DetailedUnpaidResponseRow> = Vec::new();
+    for name in &names {
+        let unpaid = name.unpaid();
+        match rows.iter_mut().find(|row| row.unpaid == unpaid) {
+            Some(row) => row.count += 1,
+            None => rows.push(DetailedUnpaidResponseRow { count: 1, unpaid }),
+        }
+    }
+    rows.sort_by_key(|row| row.unpaid);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "ok": true,
+        "count": names.len(),
+        "unpaid": rows,
+    })))
+}
+
 #[get("/{id}")]
 async fn name_get(
     state: web::Data<AppState>,
@@ -46,6 +84,65 @@ async fn name_get(
     .ok_or_else(|| KristError::Name(NameError::NameNotFound(id)))
 }
 
+#[post("/{name}")]
+async fn name_register(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<NameRegisterDetails>,
+) -> Result<HttpResponse, KristError> {
+    let name = name.into_inner();
+    let details = details.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::authorize_sender(db, details.privatekey, None, None, NAME_COST).await?;
+
+    let name = Name::register(db, &wallet.wallet, name).await?;
+    events::send_name(&state, &name, None, None).await;
+
+    Ok(HttpResponse::Ok().json(NameResponse {
+        ok: true,
+        name: name.into(),
+    }))
+}
+
+#[post("/{name}/transfer")]
+async fn name_transfer(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+    details: web::Json<NameTransferDetails>,
+) -> Result<HttpResponse, KristError> {
+    let name = name.into_inner().to_lowercase();
+    let details = details.into_inner();
+    let db = &state.db;
+
+    let sender =
+        Wallet::authorize_sender(db, details.privatekey, None, None, Decimal::ZERO).await?;
+    let recipient = Wallet::get_by_address(db, details.address.clone())
+        .await?
+        .ok_or_else(|| KristError::Address(AddressError::NotFound(details.address)))?;
+    let model = Name::get_partial(db, &name)
+        .await?
+        .ok_or_else(|| KristError::Name(NameError::NameNotFound(name)))?;
+
+    let (name, unlisted) = Name::transfer(db, &model, &sender.wallet, &recipient).await?;
+    for listing in &unlisted {
+        events::send_name(&state, &name, Some("unlisted"), Some(listing)).await;
+    }
+    events::send_name(&state, &name, None, None).await;
+
+    Ok(HttpResponse::Ok().json(NameResponse {
+        ok: true,
+        name: name.into(),
+    }))
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/names").service(name_get).service(name_list));
+    cfg.service(
+        web::scope("/names")
+            .service(name_unpaid)
+            .service(name_get)
+            .service(name_register)
+            .service(name_transfer)
+            .service(name_list),
+    );
 }
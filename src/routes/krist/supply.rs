@@ -0,0 +1,22 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::krist::KristError;
+use crate::models::stats::MoneySupplyResponse;
+use crate::AppState;
+
+#[get("/supply")]
+async fn supply_get(state: web::Data<AppState>) -> Result<HttpResponse, KristError> {
+    let db = &state.db;
+
+    let money_supply = Wallet::supply(db).await?;
+
+    Ok(HttpResponse::Ok().json(MoneySupplyResponse {
+        ok: true,
+        money_supply,
+    }))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(supply_get);
+}
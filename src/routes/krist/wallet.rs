@@ -1,8 +1,14 @@
 use actix_web::{get, web, HttpResponse};
 
+use crate::database::models::name::Model as Name;
+use crate::database::models::transaction::{
+    Model as Transaction, TransactionFilter, TransactionOrder,
+};
 use crate::database::models::wallet::Model as Wallet;
 use crate::errors::krist::{address::AddressError, KristError};
 use crate::models::addresses::{AddressJson, AddressListResponse, AddressResponse};
+use crate::models::names::{NameJson, NameListResponse};
+use crate::models::transactions::{TransactionJson, TransactionListResponse};
 use crate::{routes::PaginationParams, AppState};
 
 #[get("")]
@@ -73,28 +79,70 @@ async fn wallet_richest(
 
 #[get("/{address}/transactions")]
 async fn wallet_get_transactions(
-    _state: web::Data<AppState>,
-    _address: web::Path<String>,
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
 ) -> Result<HttpResponse, KristError> {
-    // let address = address.into_inner();
-    // let db = &state.db;
-
-    // let wallet = Wallet::get_by_address_excl(db, address).await?;
+    let address = address.into_inner();
+    let pagination = pagination.into_inner();
+    let db = &state.db;
 
-    todo!("Not yet implemented, new method required on transaction model")
+    if Wallet::get_by_address_excl(db, address.clone())
+        .await?
+        .is_none()
+    {
+        return Err(KristError::Address(AddressError::NotFound(address)));
+    }
+
+    let filter = TransactionFilter {
+        addresses: vec![address],
+        ..Default::default()
+    };
+
+    let total = Transaction::count_filtered(db, &filter).await?;
+    let transactions =
+        Transaction::filtered(db, &filter, &TransactionOrder::default(), &pagination)
+            .await?
+            .into_iter()
+            .map(|trans| trans.into())
+            .collect::<Vec<TransactionJson>>();
+
+    Ok(HttpResponse::Ok().json(TransactionListResponse {
+        ok: true,
+        count: transactions.len(),
+        total,
+        transactions,
+    }))
 }
 
 #[get("/{address}/names")]
 async fn wallet_get_names(
-    _state: web::Data<AppState>,
-    _address: web::Path<String>,
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
 ) -> Result<HttpResponse, KristError> {
-    // let address = address.into_inner();
-    // let db = &state.db;
+    let address = address.into_inner();
+    let pagination = pagination.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address.clone())
+        .await?
+        .ok_or_else(|| KristError::Address(AddressError::NotFound(address)))?;
+    let owner = wallet.id.unwrap(); // `unwrap` is fine, we selected the id.
 
-    // let wallet = Wallet::get_by_address_excl(db, address).await?;
+    let total = Name::count_by_owner(db, owner.clone()).await?;
+    let names = Name::get_by_owner(db, owner, &pagination)
+        .await?
+        .into_iter()
+        .map(|name| name.into())
+        .collect::<Vec<NameJson>>();
 
-    todo!("Not yet implemented, unsure how to approach")
+    Ok(HttpResponse::Ok().json(NameListResponse {
+        ok: true,
+        count: names.len(),
+        total,
+        names,
+    }))
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -1,5 +1,6 @@
 use actix_web::web;
 
+use crate::database::cursor::{Cursor, CursorDirection, CursorPosition};
 use crate::guards;
 
 pub mod index;
@@ -23,6 +24,35 @@ impl Default for PaginationParams {
     }
 }
 
+/// Query parameters for cursor paginated lists, see [`crate::database::cursor`].
+/// Offset pagination through [`PaginationParams`] is kept for Krist compatibility.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct CursorParams {
+    pub limit: Option<u64>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+}
+
+impl CursorParams {
+    pub fn limit(&self) -> u64 {
+        self.limit.unwrap_or(50).clamp(1, 1000)
+    }
+
+    /// Decode the given cursor, if any. Returns a validation message if the cursor is invalid.
+    pub fn position(&self) -> Result<Option<CursorPosition>, String> {
+        let (direction, raw) = match (&self.after, &self.before) {
+            (None, None) => return Ok(None),
+            (Some(after), None) => (CursorDirection::After, after),
+            (None, Some(before)) => (CursorDirection::Before, before),
+            (Some(_), Some(_)) => return Err("Only one of after and before can be given".into()),
+        };
+
+        let cursor = Cursor::decode(raw).ok_or_else(|| format!("Invalid cursor {raw}"))?;
+
+        Ok(Some(CursorPosition { direction, cursor }))
+    }
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::scope("/api/v1").configure(v1::config));
     cfg.service(web::scope("/api/krist").configure(krist::config));
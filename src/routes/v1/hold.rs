@@ -0,0 +1,163 @@
+use actix_web::{get, post, web, HttpResponse};
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use crate::database::models::hold::Model as Hold;
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::hold::HoldError;
+use crate::errors::wallet::WalletError;
+use crate::errors::KromerError;
+use crate::routes::v1::LoginDetail;
+use crate::routes::PaginationParams;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct CreateHoldDetails {
+    pub password: String,
+    /// Address of a shared wallet to reserve the funds on, if the password belongs to a wallet of one of its members.
+    pub from: Option<String>,
+    /// Address of the wallet that can capture the funds.
+    pub holder: String,
+    pub amount: Decimal,
+    /// Seconds until the hold expires and the funds are available again.
+    pub expires_in: i64,
+    pub metadata: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CaptureDetails {
+    /// Password of the holder.
+    pub password: String,
+    /// Amount to capture, everything that remains of the hold if not given.
+    pub amount: Option<Decimal>,
+    pub metadata: Option<String>,
+}
+
+#[post("")]
+async fn hold_create(
+    state: web::Data<AppState>,
+    details: web::Json<CreateHoldDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let holder = Wallet::get_by_address(db, details.holder)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    // Captures are charged the transfer fee, so it counts towards the limits of the sender.
+    let wallet = Wallet::authorize_sender(
+        db,
+        details.password,
+        details.from,
+        Some(&holder),
+        details.amount,
+    )
+    .await?;
+
+    let hold = Hold::create(
+        db,
+        &wallet.wallet,
+        &holder,
+        details.amount,
+        details.expires_in,
+        details.metadata,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[get("/wallet/{address}")]
+async fn hold_list(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let pagination = pagination.into_inner();
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let holds = Hold::get_by_wallet(db, id, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(holds))
+}
+
+#[get("/{id}")]
+async fn hold_get(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let hold = Hold::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::Hold(HoldError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+#[post("/{id}/capture")]
+async fn hold_capture(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    details: web::Json<CaptureDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let hold = held_by(&state, &id.into_inner(), details.password).await?;
+    let (hold, transaction) = Hold::capture(db, &hold, details.amount, details.metadata).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "hold": hold,
+        "transaction": transaction,
+    })))
+}
+
+#[post("/{id}/release")]
+async fn hold_release(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let hold = held_by(&state, &id.into_inner(), detail.into_inner().password).await?;
+    let hold = Hold::release(db, &hold).await?;
+
+    Ok(HttpResponse::Ok().json(hold))
+}
+
+/// Get a hold, which the password has to belong to the holder of.
+async fn held_by(state: &AppState, id: &str, password: String) -> Result<Hold, KromerError> {
+    let db = &state.db;
+
+    let hold = Hold::get_partial(db, id)
+        .await?
+        .ok_or(KromerError::Hold(HoldError::NotFound))?;
+    let holder = Wallet::verify(db, password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+    if holder.id.as_ref() != Some(&hold.holder) {
+        return Err(KromerError::Hold(HoldError::NotHolder));
+    }
+
+    Ok(hold)
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/hold")
+            .service(hold_create)
+            .service(hold_list)
+            .service(hold_get)
+            .service(hold_capture)
+            .service(hold_release),
+    );
+}
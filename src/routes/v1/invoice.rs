@@ -0,0 +1,111 @@
+use actix_web::{get, post, web, HttpResponse};
+use rust_decimal::Decimal;
+
+use crate::database::models::invoice::Model as Invoice;
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::invoice::InvoiceError;
+use crate::errors::wallet::WalletError;
+use crate::errors::KromerError;
+use crate::invoices;
+use crate::routes::v1::LoginDetail;
+use crate::routes::PaginationParams;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct CreateInvoiceDetails {
+    pub password: String,
+    pub amount: Decimal,
+    pub memo: Option<String>,
+    /// Seconds until the invoice expires, it never does if not given.
+    pub expires_in: Option<i64>,
+}
+
+#[post("")]
+async fn invoice_create(
+    state: web::Data<AppState>,
+    details: web::Json<CreateInvoiceDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let wallet = Wallet::verify(db, details.password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let invoice = Invoice::create(db, id, details.amount, details.memo, details.expires_in).await?;
+    invoices::notify(&state, &invoice, "created", None).await;
+
+    Ok(HttpResponse::Ok().json(invoice))
+}
+
+#[get("/wallet/{address}")]
+async fn invoice_list(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let pagination = pagination.into_inner();
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let invoices = Invoice::get_by_wallet(db, id, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(invoices))
+}
+
+#[get("/{id}")]
+async fn invoice_get(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let invoice = Invoice::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::Invoice(InvoiceError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(invoice))
+}
+
+#[post("/{id}/cancel")]
+async fn invoice_cancel(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let invoice = Invoice::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::Invoice(InvoiceError::NotFound))?;
+    let wallet = Wallet::verify(db, detail.into_inner().password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+    if wallet.id.as_ref() != Some(&invoice.wallet) {
+        return Err(KromerError::Invoice(InvoiceError::NotAllowed));
+    }
+
+    let invoice = Invoice::cancel(db, &invoice).await?;
+    invoices::notify(&state, &invoice, "cancelled", None).await;
+
+    Ok(HttpResponse::Ok().json(invoice))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/invoice")
+            .service(invoice_create)
+            .service(invoice_list)
+            .service(invoice_get)
+            .service(invoice_cancel),
+    );
+}
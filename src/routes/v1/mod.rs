@@ -1,6 +1,14 @@
+mod hold;
+mod invoice;
 mod name;
+mod name_listing;
+mod pending;
+mod player;
+mod standing_order;
+mod stats;
 mod transaction;
 mod wallet;
+mod webhook;
 
 use actix_web::{get, web, HttpResponse};
 
@@ -33,4 +41,12 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.configure(wallet::config);
     cfg.configure(transaction::config);
     cfg.configure(name::config);
+    cfg.configure(name_listing::config);
+    cfg.configure(player::config);
+    cfg.configure(pending::config);
+    cfg.configure(stats::config);
+    cfg.configure(webhook::config);
+    cfg.configure(invoice::config);
+    cfg.configure(hold::config);
+    cfg.configure(standing_order::config);
 }
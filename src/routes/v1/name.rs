@@ -1,5 +1,157 @@
-use actix_web::web;
+use actix_web::{get, post, web, HttpResponse};
+use rust_decimal::Decimal;
+
+use crate::database::models::name::{Model as Name, NAME_COST};
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::name::NameError;
+use crate::errors::wallet::WalletError;
+use crate::errors::KromerError;
+use crate::routes::{CursorParams, PaginationParams};
+use crate::websockets::events;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct NamePurchaseDetails {
+    pub password: String,
+    /// Address of a shared wallet to buy the name for, if the password belongs to a wallet of one of its members.
+    pub from: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NameTransferDetails {
+    pub password: String,
+    /// Address of a shared wallet owning the name, if the password belongs to a wallet of one of its members.
+    pub from: Option<String>,
+    pub name: String,
+    pub to: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NameRenewDetails {
+    pub password: String,
+    pub name: String,
+}
+
+#[get("/list")]
+async fn name_list(
+    state: web::Data<AppState>,
+    params: web::Query<CursorParams>,
+) -> Result<HttpResponse, KromerError> {
+    let params = params.into_inner();
+    let db = &state.db;
+
+    let position = params.position().map_err(KromerError::Validation)?;
+    let names = Name::page(db, params.limit(), position.as_ref()).await?;
+
+    Ok(HttpResponse::Ok().json(names))
+}
+
+#[get("/owner/{address}")]
+async fn name_list_by_owner(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let address = address.into_inner();
+    let pagination = pagination.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound))?;
+    let names = Name::get_by_owner(db, wallet.id.unwrap(), &pagination).await?; // `unwrap` is fine, we selected the id.
+
+    Ok(HttpResponse::Ok().json(names))
+}
+
+#[get("/{name}")]
+async fn name_get(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let name = name.into_inner();
+    let db = &state.db;
+
+    let name = Name::get_partial(db, name.to_lowercase()).await?;
+
+    match name {
+        Some(name) => Ok(HttpResponse::Ok().json(name)),
+        None => Err(KromerError::Name(NameError::NotFound)),
+    }
+}
+
+#[post("/purchase")]
+async fn name_purchase(
+    state: web::Data<AppState>,
+    details: web::Json<NamePurchaseDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let details = details.into_inner();
+    let db = &state.db;
+
+    let wallet =
+        Wallet::authorize_sender(db, details.password, details.from, None, NAME_COST).await?;
+
+    let name = Name::register(db, &wallet.wallet, details.name).await?;
+    events::send_name(&state, &name, None, None).await;
+
+    Ok(HttpResponse::Ok().json(name))
+}
+
+#[post("/transfer")]
+async fn name_transfer(
+    state: web::Data<AppState>,
+    details: web::Json<NameTransferDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let details = details.into_inner();
+    let db = &state.db;
+
+    let sender =
+        Wallet::authorize_sender(db, details.password, details.from, None, Decimal::ZERO).await?;
+    let recipient = Wallet::get_by_address(db, details.to)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound))?;
+    let name = Name::get_partial(db, details.name.to_lowercase())
+        .await?
+        .ok_or_else(|| KromerError::Name(NameError::NotFound))?;
+
+    let (name, unlisted) = Name::transfer(db, &name, &sender.wallet, &recipient).await?;
+    for listing in &unlisted {
+        events::send_name(&state, &name, Some("unlisted"), Some(listing)).await;
+    }
+    events::send_name(&state, &name, None, None).await;
+
+    Ok(HttpResponse::Ok().json(name))
+}
+
+#[post("/renew")]
+async fn name_renew(
+    state: web::Data<AppState>,
+    details: web::Json<NameRenewDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let details = details.into_inner();
+    let db = &state.db;
+
+    let owner = Wallet::verify(db, details.password)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::InvalidPassword))?;
+    let name = Name::get_partial(db, details.name.to_lowercase())
+        .await?
+        .ok_or_else(|| KromerError::Name(NameError::NotFound))?;
+
+    let name = Name::renew(db, &name, &owner).await?;
+
+    Ok(HttpResponse::Ok().json(name))
+}
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/name"));
+    cfg.service(
+        web::scope("/name")
+            .service(name_list)
+            .service(name_list_by_owner)
+            .service(name_purchase)
+            .service(name_transfer)
+            .service(name_renew)
+            .service(name_get),
+    );
 }
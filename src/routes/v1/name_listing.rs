@@ -0,0 +1,197 @@
+use actix_web::{get, post, web, HttpResponse};
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use crate::database::models::name::Model as Name;
+use crate::database::models::name_listing::Model as NameListing;
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::name::NameError;
+use crate::errors::name_listing::NameListingError;
+use crate::errors::wallet::WalletError;
+use crate::errors::KromerError;
+use crate::routes::v1::LoginDetail;
+use crate::routes::PaginationParams;
+use crate::websockets::events;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct CreateListingDetails {
+    /// Password of the owner of the name.
+    pub password: String,
+    pub name: String,
+    pub price: Decimal,
+    /// Address of the only wallet allowed to buy the name.
+    pub buyer: Option<String>,
+    /// Seconds until the listing expires, it never does if not given.
+    pub expires_in: Option<i64>,
+}
+
+#[post("")]
+async fn listing_create(
+    state: web::Data<AppState>,
+    details: web::Json<CreateListingDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let seller = Wallet::verify(db, details.password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+    let name = Name::get_partial(db, details.name.to_lowercase())
+        .await?
+        .ok_or(KromerError::Name(NameError::NotFound))?;
+    let buyer = match details.buyer {
+        Some(address) => Some(
+            Wallet::get_by_address(db, address)
+                .await?
+                .ok_or(KromerError::Wallet(WalletError::NotFound))?,
+        ),
+        None => None,
+    };
+
+    let listing = NameListing::create(
+        db,
+        &name,
+        &seller,
+        details.price,
+        buyer.as_ref(),
+        details.expires_in,
+    )
+    .await?;
+    events::send_name(&state, &name, Some("listed"), Some(&listing)).await;
+
+    Ok(HttpResponse::Ok().json(listing))
+}
+
+#[get("")]
+async fn listing_list(
+    state: web::Data<AppState>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let listings = NameListing::get_active(db, &pagination.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(listings))
+}
+
+#[get("/name/{name}")]
+async fn listing_for_name(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let name = Name::get_partial(db, name.into_inner().to_lowercase())
+        .await?
+        .ok_or(KromerError::Name(NameError::NotFound))?;
+    let id = name
+        .id
+        .ok_or(KromerError::Internal("Name is missing its id"))?;
+
+    let listing = NameListing::get_active_for_name(db, id)
+        .await?
+        .ok_or(KromerError::NameListing(NameListingError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(listing))
+}
+
+#[get("/wallet/{address}")]
+async fn listing_by_wallet(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let pagination = pagination.into_inner();
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let listings = NameListing::get_by_wallet(db, id, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(listings))
+}
+
+#[get("/{id}")]
+async fn listing_get(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let listing = NameListing::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::NameListing(NameListingError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(listing))
+}
+
+#[post("/{id}/buy")]
+async fn listing_buy(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let listing = NameListing::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::NameListing(NameListingError::NotFound))?;
+    let buyer = Wallet::verify(db, detail.into_inner().password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+
+    let (listing, transaction, name) = NameListing::buy(db, &listing, &buyer).await?;
+    events::send_transaction(&state, &transaction).await;
+    events::send_name(&state, &name, Some("sold"), Some(&listing)).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "listing": listing,
+        "transaction": transaction,
+        "name": name,
+    })))
+}
+
+#[post("/{id}/cancel")]
+async fn listing_cancel(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let listing = NameListing::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::NameListing(NameListingError::NotFound))?;
+    let seller = Wallet::verify(db, detail.into_inner().password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+    if seller.id.as_ref() != Some(&listing.seller) {
+        return Err(KromerError::NameListing(NameListingError::NotSeller));
+    }
+
+    let listing = NameListing::cancel(db, &listing).await?;
+    if let Some(name) = Name::get(db, listing.name.to_raw()).await? {
+        events::send_name(&state, &name, Some("unlisted"), Some(&listing)).await;
+    }
+
+    Ok(HttpResponse::Ok().json(listing))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/name_listing")
+            .service(listing_create)
+            .service(listing_list)
+            .service(listing_for_name)
+            .service(listing_by_wallet)
+            .service(listing_get)
+            .service(listing_buy)
+            .service(listing_cancel),
+    );
+}
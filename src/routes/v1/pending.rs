@@ -0,0 +1,229 @@
+use actix_web::{post, web, HttpResponse};
+use rust_decimal::Decimal;
+
+use crate::database::models::pending_transfer::{Model as PendingTransfer, PendingTransferStatus};
+use crate::database::models::transaction::Model as Transaction;
+use crate::database::models::wallet::{Model as Wallet, WalletMember, WalletRole};
+use crate::errors::pending_transfer::PendingTransferError;
+use crate::errors::wallet::WalletError;
+use crate::errors::KromerError;
+use crate::invoices;
+use crate::routes::v1::LoginDetail;
+use crate::routes::PaginationParams;
+use crate::websockets::events;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct ProposeDetails {
+    pub password: String,
+    /// Address of the shared wallet to send from.
+    pub from: String,
+    /// Address of the recipient, or `@PlayerName` to pay the primary wallet of a player.
+    pub to: String,
+    pub amount: Decimal,
+    pub metadata: Option<String>,
+}
+
+/// List the transfers proposed from a wallet, which the password has to belong to a member of.
+#[post("/wallet/{address}")]
+async fn pending_list(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let pagination = pagination.into_inner();
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    Wallet::get_member_by_password(db, detail.into_inner().password, &wallet).await?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let pending = PendingTransfer::get_by_wallet(db, id, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+#[post("/propose")]
+async fn pending_propose(
+    state: web::Data<AppState>,
+    details: web::Json<ProposeDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let wallet = Wallet::get_by_address(db, details.from)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let member = voting_member(&state, details.password, &wallet).await?;
+    let to = Wallet::resolve_recipient(db, &details.to).await?;
+
+    let pending = PendingTransfer::propose(
+        db,
+        &wallet,
+        &to,
+        details.amount,
+        details.metadata,
+        member.player,
+    )
+    .await?;
+    events::send_pending_transfer(&state, &pending, "proposed").await;
+
+    let pending = execute(&state, pending).await?;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+/// Get a pending transfer, which the password has to belong to a member of its wallet.
+#[post("/{id}")]
+async fn pending_get(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let (pending, wallet) = pending_with_wallet(&state, &id.into_inner()).await?;
+    Wallet::get_member_by_password(db, detail.into_inner().password, &wallet).await?;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+#[post("/{id}/approve")]
+async fn pending_approve(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let (pending, wallet) = pending_with_wallet(&state, &id.into_inner()).await?;
+    let member = voting_member(&state, detail.into_inner().password, &wallet).await?;
+
+    let pending = PendingTransfer::vote(db, &pending, member.player, true).await?;
+    events::send_pending_transfer(&state, &pending, "approved").await;
+
+    let pending = execute(&state, pending).await?;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+#[post("/{id}/reject")]
+async fn pending_reject(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let (pending, wallet) = pending_with_wallet(&state, &id.into_inner()).await?;
+    let member = voting_member(&state, detail.into_inner().password, &wallet).await?;
+
+    let pending = PendingTransfer::vote(db, &pending, member.player, false).await?;
+
+    let wallet_id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+    let eligible_members = Wallet::get_members(db, wallet_id)
+        .await?
+        .iter()
+        .filter(|member| member.role != WalletRole::Viewer)
+        .count();
+    let pending = PendingTransfer::reject_if_unreachable(db, pending, eligible_members).await?;
+
+    let action = match pending.status {
+        PendingTransferStatus::Rejected => "rejected",
+        _ => "rejection",
+    };
+    events::send_pending_transfer(&state, &pending, action).await;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+#[post("/{id}/cancel")]
+async fn pending_cancel(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let (pending, wallet) = pending_with_wallet(&state, &id.into_inner()).await?;
+    let member = voting_member(&state, detail.into_inner().password, &wallet).await?;
+
+    if member.player != pending.proposer && member.role != WalletRole::Owner {
+        return Err(KromerError::PendingTransfer(
+            PendingTransferError::NotAllowed,
+        ));
+    }
+
+    let pending =
+        PendingTransfer::set_status(db, &pending, PendingTransferStatus::Cancelled).await?;
+    events::send_pending_transfer(&state, &pending, "cancelled").await;
+
+    Ok(HttpResponse::Ok().json(pending))
+}
+
+/// Get a pending transfer and the wallet it is sent from.
+async fn pending_with_wallet(
+    state: &AppState,
+    id: &str,
+) -> Result<(PendingTransfer, Wallet), KromerError> {
+    let db = &state.db;
+
+    let pending = PendingTransfer::get_partial(db, id)
+        .await?
+        .ok_or(KromerError::PendingTransfer(PendingTransferError::NotFound))?;
+    let wallet = Wallet::get(db, pending.wallet.to_raw())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+
+    Ok((pending, wallet))
+}
+
+/// Get the member the password belongs to, who has to be allowed to vote on transfers from the wallet.
+async fn voting_member(
+    state: &AppState,
+    password: String,
+    wallet: &Wallet,
+) -> Result<WalletMember, KromerError> {
+    let member = Wallet::get_member_by_password(&state.db, password, wallet).await?;
+    if member.role == WalletRole::Viewer {
+        return Err(KromerError::Wallet(WalletError::NotAllowed));
+    }
+
+    Ok(member)
+}
+
+/// Execute the transfer if it has enough approvals, notifying members when it was.
+async fn execute(
+    state: &AppState,
+    pending: PendingTransfer,
+) -> Result<PendingTransfer, KromerError> {
+    let pending = PendingTransfer::execute_if_approved(&state.db, pending).await?;
+    if pending.status == PendingTransferStatus::Executed {
+        events::send_pending_transfer(state, &pending, "executed").await;
+
+        if let Some(id) = &pending.transaction {
+            match Transaction::get(&state.db, id.to_raw()).await {
+                Ok(Some(transaction)) => invoices::settle_payment(state, &transaction).await,
+                Ok(None) => {}
+                Err(e) => tracing::error!("Failed to get executed pending transfer: {e}"),
+            }
+        }
+    }
+
+    Ok(pending)
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/pending")
+            .service(pending_list)
+            .service(pending_propose)
+            .service(pending_get)
+            .service(pending_approve)
+            .service(pending_reject)
+            .service(pending_cancel),
+    );
+}
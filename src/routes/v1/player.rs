@@ -0,0 +1,66 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::database::models::player::Model as Player;
+use crate::database::models::wallet::Model as Wallet;
+use crate::errors::player::PlayerError;
+use crate::errors::KromerError;
+use crate::AppState;
+
+#[get("/by-name/{name}")]
+async fn player_get_by_name(
+    state: web::Data<AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let name = name.into_inner();
+    let db = &state.db;
+
+    let player = Player::get_by_name(db, name)
+        .await?
+        .ok_or_else(|| KromerError::Player(PlayerError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(player))
+}
+
+#[get("/{uuid}/wallets")]
+async fn player_wallets(
+    state: web::Data<AppState>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let uuid = uuid.into_inner();
+    let db = &state.db;
+
+    let player = Player::get_partial(db, uuid)
+        .await?
+        .ok_or_else(|| KromerError::Player(PlayerError::NotFound))?;
+    let id = player
+        .id
+        .ok_or(KromerError::Internal("Player is missing its id"))?;
+
+    let wallets = Wallet::get_by_player(db, id).await?;
+
+    Ok(HttpResponse::Ok().json(wallets))
+}
+
+#[get("/{uuid}")]
+async fn player_get(
+    state: web::Data<AppState>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let uuid = uuid.into_inner();
+    let db = &state.db;
+
+    let player = Player::get_partial(db, uuid)
+        .await?
+        .ok_or_else(|| KromerError::Player(PlayerError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(player))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/player")
+            .service(player_get_by_name)
+            .service(player_wallets)
+            .service(player_get),
+    );
+}
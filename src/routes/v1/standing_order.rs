@@ -0,0 +1,187 @@
+use actix_web::{get, post, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use crate::database::models::standing_order::{FailurePolicy, Model as StandingOrder};
+use crate::database::models::wallet::{Model as Wallet, WalletRole};
+use crate::errors::standing_order::StandingOrderError;
+use crate::errors::wallet::WalletError;
+use crate::errors::KromerError;
+use crate::routes::v1::LoginDetail;
+use crate::routes::PaginationParams;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct CreateStandingOrderDetails {
+    pub password: String,
+    /// Address of a shared wallet to pay from, if the password belongs to a wallet of one of its members.
+    pub from: Option<String>,
+    /// Address of the recipient, or `@PlayerName` to pay the primary wallet of a player.
+    pub to: String,
+    pub amount: Decimal,
+    /// How often to pay, like `1d`, `2w`, `1mo` or `@weekly`.
+    pub interval: String,
+    /// When the first payment is made, one interval from now if not given.
+    pub start_at: Option<DateTime<Utc>>,
+    /// When the order stops paying, it never does if not given.
+    pub end_at: Option<DateTime<Utc>>,
+    /// What to do when a payment fails, `retry` if not given.
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+    pub metadata: Option<String>,
+}
+
+#[post("")]
+async fn standing_order_create(
+    state: web::Data<AppState>,
+    details: web::Json<CreateStandingOrderDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let to = Wallet::resolve_recipient(db, &details.to).await?;
+    let from = Wallet::authorize_sender(
+        db,
+        details.password,
+        details.from,
+        Some(&to),
+        details.amount,
+    )
+    .await?;
+
+    let order = StandingOrder::create(
+        db,
+        &from,
+        &to,
+        details.amount,
+        &details.interval,
+        details.start_at,
+        details.end_at,
+        details.on_failure,
+        details.metadata,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+#[get("/wallet/{address}")]
+async fn standing_order_list(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    pagination: web::Query<PaginationParams>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let pagination = pagination.into_inner();
+
+    let wallet = Wallet::get_by_address(db, address.into_inner())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let orders = StandingOrder::get_by_wallet(db, id, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+#[get("/{id}")]
+async fn standing_order_get(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let order = StandingOrder::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::StandingOrder(StandingOrderError::NotFound))?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+#[post("/{id}/pause")]
+async fn standing_order_pause(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let order = managed_by(&state, &id.into_inner(), detail.into_inner().password).await?;
+    let order = StandingOrder::pause(db, &order).await?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+#[post("/{id}/resume")]
+async fn standing_order_resume(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let order = managed_by(&state, &id.into_inner(), detail.into_inner().password).await?;
+    let order = StandingOrder::resume(db, &order).await?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+#[post("/{id}/cancel")]
+async fn standing_order_cancel(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let order = managed_by(&state, &id.into_inner(), detail.into_inner().password).await?;
+    let order = StandingOrder::cancel(db, &order).await?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+/// Get a standing order, which the password has to belong to the wallet paying it, or to a
+/// member allowed to spend from that wallet.
+async fn managed_by(
+    state: &AppState,
+    id: &str,
+    password: String,
+) -> Result<StandingOrder, KromerError> {
+    let db = &state.db;
+
+    let order = StandingOrder::get_partial(db, id)
+        .await?
+        .ok_or(KromerError::StandingOrder(StandingOrderError::NotFound))?;
+    let credential = Wallet::verify(db, password.clone())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+    if credential.id.as_ref() == Some(&order.from) {
+        return Ok(order);
+    }
+
+    let wallet = Wallet::get(db, order.from.to_raw())
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let member = Wallet::get_member_by_password(db, password, &wallet)
+        .await
+        .map_err(|_| KromerError::StandingOrder(StandingOrderError::NotAllowed))?;
+    if member.role == WalletRole::Viewer {
+        return Err(KromerError::StandingOrder(StandingOrderError::NotAllowed));
+    }
+
+    Ok(order)
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/standing_order")
+            .service(standing_order_create)
+            .service(standing_order_list)
+            .service(standing_order_get)
+            .service(standing_order_pause)
+            .service(standing_order_resume)
+            .service(standing_order_cancel),
+    );
+}
@@ -0,0 +1,25 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::database::stats::economy_stats;
+use crate::errors::KromerError;
+use crate::AppState;
+
+#[get("/stats")]
+async fn stats_get(state: web::Data<AppState>) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    // Hold the lock while computing so concurrent pollers wait for one computation instead of all running it.
+    let mut cache = state.stats_cache.lock().await;
+    if let Some(stats) = cache.get() {
+        return Ok(HttpResponse::Ok().json(stats));
+    }
+
+    let stats = economy_stats(db).await?;
+    cache.set(stats.clone());
+
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(stats_get);
+}
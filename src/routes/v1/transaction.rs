@@ -1,39 +1,96 @@
 use actix_web::{get, post, web, HttpResponse};
 use rust_decimal::Decimal;
-use rust_decimal_macros::dec;
 
-use crate::database::models::transaction::{Model as Transaction, TransactionCreateData};
+use crate::database::models::transaction::{
+    refund_metadata, Model as Transaction, TransactionFilter,
+};
 use crate::database::models::wallet::Model as Wallet;
-
 use crate::errors::wallet::WalletError;
+
+use crate::errors::transaction::TransactionError;
+use crate::fees::FEE_SCHEDULE;
 use crate::models::transactions::TransactionType;
-use crate::{
-    errors::{transaction::TransactionError, KromerError},
-    routes::PaginationParams,
-    AppState,
-};
+use crate::websockets::events;
+use crate::{errors::KromerError, invoices, routes::CursorParams, AppState};
 
 #[derive(Debug, serde::Deserialize)]
 struct TransactionDetails {
     pub password: String,
-    pub to: String,
+    /// Address of a shared wallet to send from, if the password belongs to a wallet of one of its members.
+    pub from: Option<String>,
+    /// Address of the recipient, or `@PlayerName` to pay the primary wallet of a player.
+    pub to: Option<String>,
+    /// Name of the player whose primary wallet is paid, instead of `to`.
+    pub to_player: Option<String>,
     pub amount: Decimal,
     pub metadata: Option<String>,
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct RefundDetails {
+    /// Password of the recipient of the transaction, or of a member allowed to spend from it.
+    pub password: String,
+    /// Amount to refund, everything not refunded yet if not given.
+    pub amount: Option<Decimal>,
+    /// Why the payment was refunded, sent as `error=` in the metadata.
+    pub error: Option<String>,
+    /// Note for the sender, sent as `message=` in the metadata.
+    pub message: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct FeePreviewParams {
+    /// Address of the sender.
+    pub from: String,
+    /// Address of the recipient, or `@PlayerName` to pay the primary wallet of a player.
+    pub to: String,
+    pub amount: Decimal,
+}
+
 #[get("/list")]
 async fn transaction_list(
     state: web::Data<AppState>,
-    query: web::Query<PaginationParams>,
+    query: web::Query<CursorParams>,
+    filter: web::Query<TransactionFilter>,
 ) -> Result<HttpResponse, KromerError> {
     let params = query.into_inner();
+    let filter = filter.into_inner();
     let db = &state.db;
 
-    let transactions = Transaction::all(db, &params).await?;
+    let position = params.position().map_err(KromerError::Validation)?;
+    let transactions = Transaction::page(db, &filter, params.limit(), position.as_ref()).await?;
 
     Ok(HttpResponse::Ok().json(transactions))
 }
 
+#[get("/fee")]
+async fn transaction_fee(
+    state: web::Data<AppState>,
+    params: web::Query<FeePreviewParams>,
+) -> Result<HttpResponse, KromerError> {
+    let params = params.into_inner();
+    let db = &state.db;
+
+    if params.amount < Decimal::ZERO {
+        return Err(KromerError::Transaction(TransactionError::InvalidAmount));
+    }
+
+    let from = Wallet::get_by_address(db, params.from)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::NotFound))?;
+    let to = Wallet::resolve_recipient(db, &params.to).await?;
+
+    let fee = FEE_SCHEDULE.quote(db, &from, &to, params.amount).await?;
+    let fee_amount = fee.as_ref().map_or(Decimal::ZERO, |fee| fee.amount);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "amount": params.amount,
+        "fee": fee_amount,
+        "total": params.amount + fee_amount,
+        "treasury": fee.map(|fee| fee.treasury.address),
+    })))
+}
+
 #[get("/{id}")]
 async fn transaction_get(
     state: web::Data<AppState>,
@@ -55,36 +112,94 @@ async fn transaction_create(
     let details = details.into_inner();
     let db = &state.db;
 
-    // Check on the server so DB doesnt throw.
-    if details.amount < dec!(0.0) {
-        return Err(KromerError::Transaction(TransactionError::InvalidAmount));
-    }
+    let to = match (details.to, details.to_player) {
+        (Some(to), None) => to,
+        (None, Some(player)) => format!("@{}", player.trim_start_matches('@')),
+        (Some(_), Some(_)) => {
+            return Err(KromerError::Validation(
+                "Only one of to and to_player can be given".into(),
+            ))
+        }
+        (None, None) => return Err(KromerError::Validation("Missing recipient".into())),
+    };
 
-    let sender = Wallet::verify(db, details.password)
-        .await?
-        .ok_or_else(|| KromerError::Wallet(WalletError::InvalidPassword))?;
-    let recipient = Wallet::get_by_address(db, details.to)
-        .await?
-        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound))?;
+    let recipient = Wallet::resolve_recipient(db, &to).await?;
+    let sender = Wallet::authorize_sender(
+        db,
+        details.password,
+        details.from,
+        Some(&recipient),
+        details.amount,
+    )
+    .await?;
+
+    let transaction = Transaction::transfer(
+        db,
+        &sender.wallet,
+        sender.spender.as_ref(),
+        &recipient,
+        details.amount,
+        details.metadata,
+    )
+    .await?;
+    invoices::settle_payment(&state, &transaction).await;
+
+    Ok(HttpResponse::Ok().json(transaction))
+}
 
-    // Make sure to check the request to see if the funds are available.
-    if sender.balance < details.amount {
-        return Err(KromerError::Transaction(
-            TransactionError::InsufficientFunds,
+#[post("/{id}/refund")]
+async fn transaction_refund(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    details: web::Json<RefundDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let details = details.into_inner();
+    let db = &state.db;
+
+    let transaction = Transaction::get_partial(db, id.into_inner())
+        .await?
+        .ok_or(KromerError::Transaction(TransactionError::NotFound))?;
+    if !matches!(
+        transaction.transaction_type,
+        TransactionType::Transfer | TransactionType::Fee
+    ) {
+        return Err(KromerError::Validation(
+            "Only transfers and fees can be refunded".into(),
         ));
     }
+    if transaction.refund_of.is_some() {
+        return Err(KromerError::Validation("Refunds can't be refunded".into()));
+    }
 
-    let creation_data = TransactionCreateData {
-        from: sender.id.unwrap(), // `unwrap` should be fine here, we already made sure it exists.
-        to: recipient.id.unwrap(),
-        amount: details.amount,
-        metadata: details.metadata,
-        transaction_type: TransactionType::Transfer,
+    let amount = match details.amount {
+        Some(amount) => amount,
+        None => transaction.amount - Transaction::refunded_amount(db, &transaction).await?,
     };
-    let response: Vec<Transaction> = db.insert("transaction").content(creation_data).await?;
-    let response = response.first().unwrap(); // the fuck man
+    let recipient = transaction.recipient_wallet(db).await?;
+    // Refunds are not charged a fee.
+    let sender =
+        Wallet::authorize_sender(db, details.password, Some(recipient.address), None, amount)
+            .await?;
+    let (target, name_target) = transaction.refund_target(db).await?;
+
+    let refunded = transaction
+        .id
+        .as_ref()
+        .ok_or(KromerError::Internal("Transaction is missing its id"))?;
+    let metadata = refund_metadata(
+        name_target.as_deref(),
+        refunded,
+        &[
+            ("error", details.error.as_deref().unwrap_or_default()),
+            ("message", details.message.as_deref().unwrap_or_default()),
+        ],
+    );
+
+    let refund =
+        Transaction::refund(db, &transaction, &sender.wallet, &target, amount, metadata).await?;
+    events::send_transaction(&state, &refund).await;
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(refund))
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -92,6 +207,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         web::scope("/transaction")
             .service(transaction_list)
             .service(transaction_create)
+            .service(transaction_refund)
+            .service(transaction_fee)
             .service(transaction_get),
     );
 }
@@ -1,14 +1,34 @@
 use actix_web::{get, post, web, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
 use serde_json::json;
 
+use crate::database::models::balance_snapshot::{Model as BalanceSnapshot, Resolution};
+use crate::database::models::player::Model as Player;
 use crate::database::models::wallet::Model as Wallet;
 use crate::errors::wallet::WalletError;
 use crate::errors::KromerError;
-use crate::routes::PaginationParams;
+use crate::routes::{CursorParams, PaginationParams};
+use crate::websockets::ws_manager;
 use crate::AppState;
 
 use crate::routes::v1::LoginDetail;
 
+#[derive(Debug, serde::Deserialize)]
+struct BalanceAtQuery {
+    /// Defaults to now.
+    at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BalanceHistoryQuery {
+    /// Defaults to 30 days, or 2 days when hourly, before `until`.
+    since: Option<DateTime<Utc>>,
+    /// Defaults to now.
+    until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    resolution: Resolution,
+}
+
 #[post("/verify")]
 async fn wallet_verify(
     state: web::Data<AppState>,
@@ -26,15 +46,40 @@ async fn wallet_verify(
     })))
 }
 
+#[post("/rotate")]
+async fn wallet_rotate(
+    state: web::Data<AppState>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let detail = detail.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::verify(db, detail.password)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::InvalidPassword))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let password = Wallet::rotate_key(db, id).await?;
+    ws_manager::invalidate_address(&state, &wallet.address).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "address": wallet.address,
+        "password": password
+    })))
+}
+
 #[get("/list")]
 async fn wallet_list(
     state: web::Data<AppState>,
-    pagination: web::Query<PaginationParams>,
+    params: web::Query<CursorParams>,
 ) -> Result<HttpResponse, KromerError> {
-    let pagination = pagination.into_inner();
+    let params = params.into_inner();
     let db = &state.db;
 
-    let wallets = Wallet::all(db, &pagination).await?;
+    let position = params.position().map_err(KromerError::Validation)?;
+    let wallets = Wallet::page(db, params.limit(), position.as_ref()).await?;
 
     Ok(HttpResponse::Ok().json(wallets))
 }
@@ -52,6 +97,77 @@ async fn wallet_richest(
     Ok(HttpResponse::Ok().json(wallets))
 }
 
+#[get("/{address}/owners")]
+async fn wallet_owners(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+) -> Result<HttpResponse, KromerError> {
+    let address = address.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound))?;
+    let id = wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))?;
+
+    let owners = Player::get_by_wallet(db, id).await?;
+
+    Ok(HttpResponse::Ok().json(owners))
+}
+
+#[get("/{address}/balance")]
+async fn wallet_balance_at(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<BalanceAtQuery>,
+) -> Result<HttpResponse, KromerError> {
+    let address = address.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound))?;
+
+    let at = query.at.unwrap_or_else(Utc::now);
+    let balance = BalanceSnapshot::balance_at(db, &wallet, at).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "address": wallet.address,
+        "at": at,
+        "balance": balance
+    })))
+}
+
+#[get("/{address}/balance/history")]
+async fn wallet_balance_history(
+    state: web::Data<AppState>,
+    address: web::Path<String>,
+    query: web::Query<BalanceHistoryQuery>,
+) -> Result<HttpResponse, KromerError> {
+    let address = address.into_inner();
+    let query = query.into_inner();
+    let db = &state.db;
+
+    let wallet = Wallet::get_by_address(db, address)
+        .await?
+        .ok_or_else(|| KromerError::Wallet(WalletError::NotFound))?;
+
+    let until = query.until.unwrap_or_else(Utc::now);
+    let since = query.since.unwrap_or_else(|| match query.resolution {
+        Resolution::Hourly => until - Duration::days(2),
+        Resolution::Daily => until - Duration::days(30),
+    });
+    let points = BalanceSnapshot::series(db, &wallet, since, until, query.resolution).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "address": wallet.address,
+        "resolution": query.resolution,
+        "points": points
+    })))
+}
+
 #[get("/{address}")]
 async fn wallet_get(
     state: web::Data<AppState>,
@@ -72,8 +188,12 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/wallet")
             .service(wallet_verify)
+            .service(wallet_rotate)
             .service(wallet_list)
             .service(wallet_richest)
+            .service(wallet_owners)
+            .service(wallet_balance_at)
+            .service(wallet_balance_history)
             .service(wallet_get),
     );
 }
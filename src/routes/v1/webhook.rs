@@ -0,0 +1,94 @@
+use actix_web::{delete, post, web, HttpResponse};
+use serde_json::json;
+
+use crate::database::models::wallet::Model as Wallet;
+use crate::database::models::webhook::{Model as Webhook, WebhookEvent};
+use crate::errors::wallet::WalletError;
+use crate::errors::webhook::WebhookError;
+use crate::errors::KromerError;
+use crate::routes::v1::LoginDetail;
+use crate::AppState;
+
+#[derive(Debug, serde::Deserialize)]
+struct CreateWebhookDetails {
+    pub password: String,
+    pub url: String,
+    /// Key to sign the request bodies with, a random one is generated if not given.
+    pub secret: Option<String>,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[post("")]
+async fn webhook_create(
+    state: web::Data<AppState>,
+    details: web::Json<CreateWebhookDetails>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+    let details = details.into_inner();
+
+    let wallet = authenticate(&state, details.password).await?;
+    let webhook = Webhook::create(db, wallet, details.url, details.secret, details.events).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "secret": webhook.secret,
+        "webhook": webhook,
+    })))
+}
+
+#[post("/list")]
+async fn webhook_list(
+    state: web::Data<AppState>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let wallet = authenticate(&state, detail.into_inner().password).await?;
+    let webhooks = Webhook::get_by_wallet(db, wallet).await?;
+
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+#[delete("/{id}")]
+async fn webhook_delete(
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    detail: web::Json<LoginDetail>,
+) -> Result<HttpResponse, KromerError> {
+    let db = &state.db;
+
+    let wallet = authenticate(&state, detail.into_inner().password).await?;
+    let webhook = Webhook::get_partial(db, id.into_inner())
+        .await?
+        .filter(|webhook| webhook.wallet == wallet)
+        .ok_or(KromerError::Webhook(WebhookError::NotFound))?;
+    let id = webhook
+        .id
+        .ok_or(KromerError::Internal("Webhook is missing its id"))?;
+
+    Webhook::delete(db, id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "ok": true })))
+}
+
+/// Get the ID of the wallet the password belongs to.
+async fn authenticate(
+    state: &AppState,
+    password: String,
+) -> Result<surrealdb::sql::Thing, KromerError> {
+    let wallet = Wallet::verify(&state.db, password)
+        .await?
+        .ok_or(KromerError::Wallet(WalletError::InvalidPassword))?;
+
+    wallet
+        .id
+        .ok_or(KromerError::Internal("Wallet is missing its id"))
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/webhook")
+            .service(webhook_create)
+            .service(webhook_list)
+            .service(webhook_delete),
+    );
+}
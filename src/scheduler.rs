@@ -0,0 +1,191 @@
+//! Scheduled background work: payment of standing orders, made through the normal transfer path,
+//! retries of failed invoice refunds, the expiry of names under rent and of pending transfers, and
+//! snapshots of wallet balances for balance history.
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tokio::task::JoinHandle;
+
+use crate::database::models::balance_snapshot::Model as BalanceSnapshot;
+use crate::database::models::name::Model as Name;
+use crate::database::models::pending_transfer::Model as PendingTransfer;
+use crate::database::models::standing_order::Model as StandingOrder;
+use crate::invoices;
+use crate::websockets::events;
+use crate::AppState;
+
+/// How often the worker looks for due standing orders.
+static STANDING_ORDER_POLL_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("STANDING_ORDER_POLL_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(30);
+    Duration::from_secs(seconds)
+});
+
+/// How often failed invoice refunds are retried.
+static INVOICE_REFUND_RETRY_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("INVOICE_REFUND_RETRY_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(300);
+    Duration::from_secs(seconds)
+});
+
+/// How often names are checked for expiry.
+static NAME_SWEEP_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("NAME_SWEEP_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(300);
+    Duration::from_secs(seconds)
+});
+
+/// How often pending transfers are checked for expiry.
+static PENDING_TRANSFER_EXPIRY_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("PENDING_TRANSFER_EXPIRY_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(60);
+    Duration::from_secs(seconds)
+});
+
+/// How often the balances of active wallets are snapshotted.
+static BALANCE_SNAPSHOT_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let hours = env::var("BALANCE_SNAPSHOT_INTERVAL_HOURS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|hours| *hours > 0)
+        .unwrap_or(24);
+    Duration::from_secs(hours * 60 * 60)
+});
+
+/// Orders paid per round of the worker.
+const BATCH_SIZE: u64 = 50;
+
+/// Start the background worker paying due standing orders.
+pub fn spawn_worker(state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*STANDING_ORDER_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let orders = match StandingOrder::due(&state.db, BATCH_SIZE).await {
+                Ok(orders) => orders,
+                Err(e) => {
+                    tracing::error!("Failed to get due standing orders: {e}");
+                    continue;
+                }
+            };
+
+            for order in orders {
+                match order.execute(&state.db).await {
+                    Ok((_, Some(transaction))) => {
+                        invoices::settle_payment(&state, &transaction).await
+                    }
+                    Ok((order, None)) => tracing::debug!(
+                        "Standing order payment failed: {}",
+                        order.last_error.unwrap_or_default()
+                    ),
+                    Err(e) => tracing::error!("Failed to run standing order: {e}"),
+                }
+            }
+        }
+    })
+}
+
+/// Start the background worker retrying invoice refunds that failed when the payment was settled.
+pub fn spawn_refund_retries(state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*INVOICE_REFUND_RETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            invoices::retry_refunds(&state).await;
+        }
+    })
+}
+
+/// Start the background worker expiring names that were not renewed and releasing them after
+/// their grace period, notifying subscribers about both.
+pub fn spawn_name_sweep(state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*NAME_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let result = match Name::sweep(&state.db).await {
+                Ok(result) => result,
+                Err(e) => {
+                    tracing::error!("Failed to sweep expired names: {e}");
+                    continue;
+                }
+            };
+
+            for name in &result.expired {
+                events::send_name(&state, name, Some("expired"), None).await;
+            }
+            for listing in &result.unlisted {
+                if let Some(name) = result
+                    .released
+                    .iter()
+                    .find(|name| name.id.as_ref() == Some(&listing.name))
+                {
+                    events::send_name(&state, name, Some("unlisted"), Some(listing)).await;
+                }
+            }
+            for name in &result.released {
+                tracing::info!("Released name {} after it expired", name.name);
+                events::send_name(&state, name, Some("released"), None).await;
+            }
+        }
+    })
+}
+
+/// Start the background worker expiring pending transfers that weren't decided in time, notifying
+/// the members of their wallets.
+pub fn spawn_pending_transfer_expiry(state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*PENDING_TRANSFER_EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let expired = match PendingTransfer::expire_stale(&state.db).await {
+                Ok(expired) => expired,
+                Err(e) => {
+                    tracing::error!("Failed to expire pending transfers: {e}");
+                    continue;
+                }
+            };
+
+            for pending in &expired {
+                events::send_pending_transfer(&state, pending, "expired").await;
+            }
+        }
+    })
+}
+
+/// Start the background worker snapshotting the balance of every wallet with transactions since
+/// the previous snapshot, so balance history only needs the transactions after the latest one.
+pub fn spawn_balance_snapshots(state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(*BALANCE_SNAPSHOT_INTERVAL);
+        let mut since = chrono::Utc::now()
+            - chrono::Duration::from_std(*BALANCE_SNAPSHOT_INTERVAL).unwrap_or_default();
+        loop {
+            interval.tick().await;
+
+            let now = chrono::Utc::now();
+            match BalanceSnapshot::take(&state.db, since).await {
+                Ok(count) => {
+                    tracing::debug!("Took {count} balance snapshots");
+                    since = now;
+                }
+                Err(e) => tracing::error!("Failed to take balance snapshots: {e}"),
+            }
+        }
+    })
+}
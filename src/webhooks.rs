@@ -0,0 +1,504 @@
+//! Delivery of webhook notifications. Deliveries are queued by the `webhook_enqueue` database
+//! event whenever a transaction is created, and sent by a background worker.
+
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use surrealdb::{engine::any::Any, Surreal};
+use tokio::task::JoinHandle;
+
+use crate::database::models::transaction::Model as Transaction;
+use crate::database::models::webhook::{Delivery, Model as Webhook, WebhookEvent};
+use crate::errors::webhook::WebhookError;
+use crate::models::transactions::TransactionJson;
+
+/// Header carrying the hex encoded HMAC-SHA256 of the request body, prefixed with `sha256=`.
+pub const SIGNATURE_HEADER: &str = "X-Kromer-Signature";
+pub const EVENT_HEADER: &str = "X-Kromer-Event";
+pub const DELIVERY_HEADER: &str = "X-Kromer-Delivery";
+
+/// How often the worker looks for due deliveries.
+static WEBHOOK_POLL_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("WEBHOOK_POLL_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(5);
+    Duration::from_secs(seconds)
+});
+
+/// How long to wait for a webhook endpoint to respond.
+static WEBHOOK_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+    let seconds = env::var("WEBHOOK_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(10);
+    Duration::from_secs(seconds)
+});
+
+/// How many deliveries the worker sends at the same time.
+static WEBHOOK_CONCURRENCY: Lazy<usize> = Lazy::new(|| {
+    env::var("WEBHOOK_CONCURRENCY")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|concurrency| *concurrency > 0)
+        .unwrap_or(8)
+});
+
+/// Hosts webhooks may be sent to even though they resolve to loopback or private addresses.
+static WEBHOOK_ALLOWED_HOSTS: Lazy<Vec<String>> = Lazy::new(|| {
+    env::var("WEBHOOK_ALLOWED_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|host| host.trim().to_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+});
+
+/// Deliveries sent per round of the worker.
+const BATCH_SIZE: u64 = 50;
+
+#[derive(Debug, serde::Serialize)]
+struct WebhookPayload<'a> {
+    /// ID of the delivery, which stays the same across retries so receivers can ignore duplicates.
+    id: String,
+    event: WebhookEvent,
+    transaction: &'a TransactionJson,
+}
+
+/// Sign a request body with the secret of a webhook.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Whether an address can be reached from the public internet, so webhooks can't be pointed at
+/// the server itself or at services on its network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space used for carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local addresses, fc00::/7.
+                || (first & 0xfe00) == 0xfc00
+                // Link-local addresses, fe80::/10.
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Host of a URL, without the brackets around IPv6 addresses.
+fn url_host(url: &reqwest::Url) -> Option<&str> {
+    url.host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+}
+
+fn is_allowed_host(host: &str) -> bool {
+    WEBHOOK_ALLOWED_HOSTS
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(host))
+}
+
+/// Check that a webhook URL is HTTP(S) and that its host only resolves to public addresses,
+/// unless the host is allowed by `WEBHOOK_ALLOWED_HOSTS`.
+pub async fn check_url(url: &str) -> Result<(), WebhookError> {
+    let invalid = || WebhookError::InvalidUrl(url.to_string());
+
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid());
+    }
+    let host = url_host(&parsed).ok_or_else(invalid)?;
+    if is_allowed_host(host) {
+        return Ok(());
+    }
+    let port = parsed.port_or_known_default().ok_or_else(invalid)?;
+
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| invalid())?
+        .collect();
+    if addresses.is_empty() || !addresses.iter().all(|address| is_public(address.ip())) {
+        return Err(WebhookError::PrivateUrl(url.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Resolver for the worker's HTTP client that drops non-public addresses, so a host that was
+/// public when its webhook was registered can't be re-pointed at a private address later.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let allowed = is_allowed_host(host);
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|address| allowed || is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{host} does not resolve to a public address").into());
+            }
+
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+/// Start the background worker sending queued deliveries, several at a time.
+pub fn spawn_worker(db: Arc<Surreal<Any>>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(*WEBHOOK_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build();
+        let client = match client {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("Failed to create webhook HTTP client: {e}");
+                return;
+            }
+        };
+
+        let mut interval = tokio::time::interval(*WEBHOOK_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let deliveries = match Delivery::due(&db, BATCH_SIZE).await {
+                Ok(deliveries) => deliveries,
+                Err(e) => {
+                    tracing::error!("Failed to get due webhook deliveries: {e}");
+                    continue;
+                }
+            };
+
+            futures_util::stream::iter(deliveries)
+                .for_each_concurrent(*WEBHOOK_CONCURRENCY, |delivery| {
+                    let db = &db;
+                    let client = &client;
+                    async move {
+                        if let Err(e) = attempt(db, client, &delivery).await {
+                            tracing::error!("Failed to record webhook delivery attempt: {e}");
+                        }
+                    }
+                })
+                .await;
+        }
+    })
+}
+
+/// Attempt a single delivery and record the outcome.
+async fn attempt(
+    db: &Surreal<Any>,
+    client: &reqwest::Client,
+    delivery: &Delivery,
+) -> Result<(), surrealdb::Error> {
+    let webhook = Webhook::get_partial(db, delivery.webhook.id.to_raw()).await?;
+    let Some(webhook) = webhook else {
+        return delivery
+            .mark_failed(db, None, "Webhook was removed".into())
+            .await;
+    };
+
+    let transaction = Transaction::get(db, delivery.transaction.to_raw()).await?;
+    let Some(transaction) = transaction else {
+        return delivery
+            .mark_failed(db, None, "Transaction not found".into())
+            .await;
+    };
+
+    let delivery_id = delivery
+        .id
+        .as_ref()
+        .map(|id| id.to_raw())
+        .unwrap_or_default();
+    let payload = WebhookPayload {
+        id: delivery_id.clone(),
+        event: delivery.event,
+        transaction: &TransactionJson::from(transaction),
+    };
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => return delivery.mark_failed(db, None, e.to_string()).await,
+    };
+    let signature = format!("sha256={}", sign(&webhook.secret, &body));
+    let event = serde_json::to_value(delivery.event)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    // Hosts given as addresses never reach the resolver, so they are checked here instead.
+    let literal = reqwest::Url::parse(&webhook.url).ok().and_then(|url| {
+        url_host(&url)
+            .filter(|host| !is_allowed_host(host))
+            .and_then(|host| host.parse::<IpAddr>().ok())
+    });
+    if literal.is_some_and(|ip| !is_public(ip)) {
+        return delivery
+            .mark_failed(db, None, "Webhook URL is not a public address".into())
+            .await;
+    }
+
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_HEADER, event)
+        .header(DELIVERY_HEADER, delivery_id)
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => {
+            delivery
+                .mark_delivered(db, response.status().as_u16())
+                .await
+        }
+        Ok(response) => {
+            let status = response.status();
+            delivery
+                .mark_failed(
+                    db,
+                    Some(status.as_u16()),
+                    format!("Endpoint responded with {status}"),
+                )
+                .await
+        }
+        Err(e) => delivery.mark_failed(db, None, e.to_string()).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::webhook::{retry_delay, DeliveryStatus, WEBHOOK_MAX_ATTEMPTS};
+    use chrono::Utc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A received request, with lowercase header names.
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Start a local HTTP stand-in for a webhook endpoint answering requests with the given
+    /// statuses in order. Returns its URL and the requests it received once all were answered.
+    async fn stand_in(statuses: Vec<u16>) -> (String, JoinHandle<Vec<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        // Named rather than given as an address, which deliveries refuse for loopback.
+        let url = format!(
+            "http://localhost:{}/hook",
+            listener.local_addr().unwrap().port()
+        );
+
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                let head_end = loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end;
+                    }
+                };
+
+                let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+                let headers: Vec<(String, String)> = head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+                    .collect();
+                let length: usize = headers
+                    .iter()
+                    .find(|(key, _)| key == "content-length")
+                    .and_then(|(_, value)| value.parse().ok())
+                    .unwrap_or(0);
+
+                let mut body = request[head_end + 4..].to_vec();
+                while body.len() < length {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    body.extend_from_slice(&buffer[..read]);
+                }
+
+                let response = format!(
+                    "HTTP/1.1 {status} Stand-in\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                received.push(Received { headers, body });
+            }
+            received
+        });
+
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_attempt_against_local_endpoint() {
+        let max_attempts = *WEBHOOK_MAX_ATTEMPTS;
+        let mut statuses = vec![200];
+        statuses.extend(std::iter::repeat_n(500, max_attempts as usize));
+        let (url, server) = stand_in(statuses).await;
+
+        let db = surrealdb::engine::any::connect("mem://").await.unwrap();
+        db.use_ns("test").use_db("test").await.unwrap();
+        db.query(include_str!(
+            "../surrealdb-migrations/schemas/webhook.surql"
+        ))
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        db.query(
+            r#"
+            CREATE transaction:payment CONTENT { from: wallet:alice, to: wallet:bob, amount: 5dec, metadata: 'hello', transaction_type: 'transfer', timestamp: time::now() };
+            CREATE webhook:hook CONTENT { wallet: wallet:bob, url: $url, secret: 'secret', events: ['incoming'] };
+            CREATE webhook_delivery:delivered CONTENT { webhook: webhook:hook, event: 'incoming', transaction: transaction:payment };
+            CREATE webhook_delivery:failing CONTENT { webhook: webhook:hook, event: 'incoming', transaction: transaction:payment };
+        "#,
+        )
+        .bind(("url", url))
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        let client = reqwest::Client::new();
+
+        let delivery = Delivery::get_partial(&db, "delivered")
+            .await
+            .unwrap()
+            .unwrap();
+        attempt(&db, &client, &delivery).await.unwrap();
+        let delivery = Delivery::get_partial(&db, "delivered")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.last_status, Some(200));
+
+        for attempts in 1..=max_attempts {
+            let delivery = Delivery::get_partial(&db, "failing")
+                .await
+                .unwrap()
+                .unwrap();
+            let before = Utc::now();
+            attempt(&db, &client, &delivery).await.unwrap();
+
+            let delivery = Delivery::get_partial(&db, "failing")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(delivery.attempts, attempts);
+            assert_eq!(delivery.last_status, Some(500));
+            if attempts < max_attempts {
+                assert_eq!(delivery.status, DeliveryStatus::Pending);
+                assert!(*delivery.next_attempt_at >= before + retry_delay(attempts));
+            } else {
+                assert_eq!(delivery.status, DeliveryStatus::Dead);
+            }
+        }
+
+        let received = server.await.unwrap();
+        assert_eq!(received.len(), max_attempts as usize + 1);
+
+        let request = &received[0];
+        let signature = format!("sha256={}", sign("secret", &request.body));
+        assert_eq!(
+            request.header("x-kromer-signature"),
+            Some(signature.as_str())
+        );
+        assert_eq!(request.header("x-kromer-event"), Some("incoming"));
+        assert_eq!(
+            request.header("x-kromer-delivery"),
+            Some("webhook_delivery:delivered")
+        );
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["id"], "webhook_delivery:delivered");
+        assert_eq!(body["event"], "incoming");
+        assert_eq!(body["transaction"]["metadata"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        check_url("https://93.184.215.14/hook").await.unwrap();
+
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://localhost:8080/hook",
+            "http://10.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+        ] {
+            assert!(
+                matches!(check_url(url).await, Err(WebhookError::PrivateUrl(_))),
+                "{url} should be rejected"
+            );
+        }
+
+        assert!(matches!(
+            check_url("ftp://example.com/hook").await,
+            Err(WebhookError::InvalidUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_sign() {
+        let signature = sign("key", b"The quick brown fox jumps over the lazy dog");
+
+        assert_eq!(
+            signature,
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
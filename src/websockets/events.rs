@@ -0,0 +1,153 @@
+use surrealdb::Uuid;
+
+use crate::database::models::name::Model as Name;
+use crate::database::models::name_listing::Model as NameListing;
+use crate::database::models::pending_transfer::Model as PendingTransfer;
+use crate::database::models::transaction::Model as Transaction;
+use crate::database::models::wallet::Model as Wallet;
+use crate::models::names::NameJson;
+use crate::models::transactions::TransactionJson;
+use crate::models::websockets::{WebSocketEventMessage, WebSocketEventType};
+use crate::websockets::types::common::WebSocketSubscriptionType;
+use crate::AppState;
+
+/// Send an event to every websocket session logged in as one of the given addresses.
+pub async fn send_to_addresses(state: &AppState, addresses: &[String], event: WebSocketEventType) {
+    let sessions: Vec<Uuid> = {
+        let manager = state.ws_manager.lock().await;
+        manager
+            .sockets
+            .values()
+            .filter(|ws| addresses.contains(&ws.address))
+            .map(|ws| ws.token)
+            .collect()
+    };
+
+    send_to_sessions(state, &sessions, event).await;
+}
+
+/// Send a transaction event to every websocket session subscribed to all transactions, and to
+/// sessions subscribed to their own transactions when logged in as the sender or the recipient.
+pub async fn send_transaction(state: &AppState, transaction: &Transaction) {
+    let mut addresses = Vec::with_capacity(2);
+    for wallet in [&transaction.from, &transaction.to] {
+        match Wallet::get(&state.db, wallet.to_raw()).await {
+            Ok(Some(wallet)) => addresses.push(wallet.address),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to get wallet to notify about transaction: {e}"),
+        }
+    }
+
+    let sessions: Vec<Uuid> = {
+        let manager = state.ws_manager.lock().await;
+        manager
+            .sockets
+            .values()
+            .filter(|ws| {
+                let subscriptions = &ws.subs.subscriptions;
+                subscriptions.contains(&WebSocketSubscriptionType::Transactions)
+                    || (subscriptions.contains(&WebSocketSubscriptionType::OwnTransactions)
+                        && addresses.contains(&ws.address))
+            })
+            .map(|ws| ws.token)
+            .collect()
+    };
+
+    let event = WebSocketEventType::Transaction {
+        transaction: TransactionJson::from(transaction.clone()),
+    };
+    send_to_sessions(state, &sessions, event).await;
+}
+
+/// Send a name event to every websocket session subscribed to all names, and to sessions
+/// subscribed to their own names when logged in as the owner or a party to the listing.
+/// Registrations and transfers are sent without an action, names that were `released` without an owner.
+pub async fn send_name(
+    state: &AppState,
+    name: &Name,
+    action: Option<&str>,
+    listing: Option<&NameListing>,
+) {
+    let mut wallets = vec![name.owner.clone()];
+    if let Some(listing) = listing {
+        wallets.push(listing.seller.clone());
+        wallets.extend(listing.buyer.clone());
+    }
+
+    let mut addresses = Vec::with_capacity(wallets.len());
+    for wallet in wallets {
+        match Wallet::get(&state.db, wallet.to_raw()).await {
+            Ok(Some(wallet)) => addresses.push(wallet.address),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to get wallet to notify about name: {e}"),
+        }
+    }
+
+    let sessions: Vec<Uuid> = {
+        let manager = state.ws_manager.lock().await;
+        manager
+            .sockets
+            .values()
+            .filter(|ws| {
+                let subscriptions = &ws.subs.subscriptions;
+                subscriptions.contains(&WebSocketSubscriptionType::Names)
+                    || (subscriptions.contains(&WebSocketSubscriptionType::OwnNames)
+                        && addresses.contains(&ws.address))
+            })
+            .map(|ws| ws.token)
+            .collect()
+    };
+
+    let mut json = NameJson::from(name.clone());
+    if action == Some("released") {
+        json.owner = None;
+    }
+    let event = WebSocketEventType::Name {
+        name: json,
+        action: action.map(str::to_string),
+        listing: listing.map(|listing| Box::new(listing.clone())),
+    };
+    send_to_sessions(state, &sessions, event).await;
+}
+
+/// Notify the members of the wallet about a change to a pending transfer.
+pub async fn send_pending_transfer(state: &AppState, pending: &PendingTransfer, action: &str) {
+    let addresses =
+        match PendingTransfer::notification_addresses(&state.db, pending.wallet.clone()).await {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                tracing::error!("Failed to get addresses to notify about pending transfer: {e}");
+                return;
+            }
+        };
+
+    let event = WebSocketEventType::PendingTransfer {
+        action: action.to_string(),
+        pending_transfer: Box::new(pending.clone()),
+    };
+    send_to_addresses(state, &addresses, event).await;
+}
+
+async fn send_to_sessions(state: &AppState, sessions: &[Uuid], event: WebSocketEventType) {
+    let message = WebSocketEventMessage {
+        message_type: "event".to_string(),
+        event,
+    };
+    let message = match serde_json::to_string(&message) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::error!("Failed to serialize websocket event: {e}");
+            return;
+        }
+    };
+
+    for &session in sessions {
+        if let Err(e) = state
+            .ws_server_handle
+            .send_message_by_session_uuid(session, message.clone())
+            .await
+        {
+            tracing::warn!("Failed to send websocket event to session {session}: {e}");
+        }
+    }
+}
@@ -19,7 +19,6 @@ use crate::{
 };
 use std::{
     pin::pin,
-    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -38,7 +37,7 @@ use futures_util::{
     StreamExt,
 };
 use serde_json::json;
-use surrealdb::{engine::any::Any, Surreal, Uuid};
+use surrealdb::Uuid;
 use tokio::{sync::mpsc, task::JoinHandle, time::interval};
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
@@ -67,6 +66,9 @@ pub async fn handle_ws(
         Err(_) => return Err(KromerError::WebSocket(WebSocketError::HandshakeError)),
     };
 
+    // Track the session so events can be sent to it by address
+    state.ws_manager.lock().await.sockets.insert(wrapped_ws_data.token, wrapped_ws_data.clone());
+
     let msg_stream = msg_stream
         .max_frame_size(64 * 1024)
         .aggregate_continuations()
@@ -123,8 +125,14 @@ pub async fn handle_ws(
                             let _ = session.text(error_msg).await;
                         } else {
                             tracing::info!("Message received: {text}");
+
+                            // The session may have been logged out elsewhere, e.g. after a key rotation
+                            if let Some(current) = state.ws_manager.lock().await.sockets.get(&ws_metadata.token) {
+                                ws_metadata = current.clone();
+                            }
+
                             let process_result = process_text_msg(
-                                &state.db,
+                                &state,
                                 &ws_metadata,
                                 &ws_server,
                                 &mut session,
@@ -136,6 +144,7 @@ pub async fn handle_ws(
                             // TODO: Might need to be a global mutex so subscriptions have access to this as well.
                             if let Ok(Some(new_metadata)) = process_result {
                                 ws_metadata = new_metadata;
+                                state.ws_manager.lock().await.sockets.insert(ws_metadata.token, ws_metadata.clone());
                             } else if process_result.is_err() {
                                 tracing::error!("Error in processing message")
                             }
@@ -187,6 +196,7 @@ pub async fn handle_ws(
 
     keepalive_abort_handle.abort();
 
+    state.ws_manager.lock().await.remove(ws_metadata.token);
     let _ = ws_server.disconnect(channel_id);
 
     let _ = session.close(close_reason).await;
@@ -195,12 +205,14 @@ pub async fn handle_ws(
 }
 
 async fn process_text_msg(
-    db: &Arc<Surreal<Any>>,
+    state: &AppState,
     ws_metadata: &WrappedWsData,
     _ws_server: &WsServerHandle,
     session: &mut actix_ws::Session,
     text: &str,
 ) -> Result<Option<WrappedWsData>, KromerError> {
+    let db = &state.db;
+
     // strip leading and trailing whitespace (spaces, newlines, etc.)
     let msg = text.trim();
 
@@ -291,11 +303,14 @@ async fn process_text_msg(
         WebSocketMessageType::MakeTransaction {
             private_key,
             to,
+            to_player,
             amount,
             metadata,
             request_id
         } => {
-            ws_modification_data = make_transaction(db, msg_id, private_key, to, amount, metadata, request_id).await;
+            // `to_player` is the same as paying `@PlayerName`
+            let to = to.or_else(|| to_player.map(|player| format!("@{}", player.trim_start_matches('@'))));
+            ws_modification_data = make_transaction(state, msg_id, private_key, to, amount, metadata, request_id).await;
         }
 
         WebSocketMessageType::Subscribe { event } => {
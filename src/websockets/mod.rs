@@ -1,3 +1,4 @@
+pub mod events;
 pub mod handler;
 pub mod routes;
 pub mod token_cache;
@@ -1,16 +1,14 @@
-use std::sync::Arc;
-
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use surrealdb::{engine::any::Any, Surreal};
 
-use crate::{database::models::transaction::TransactionCreateData, models::{error::ErrorResponse, transactions::TransactionType, websockets::{OutgoingWebSocketMessage, ResponseMessageType, WebSocketMessageType, WsSessionModification}}, websockets::utils::datetime::convert_to_iso_string};
+use crate::{errors::{player::PlayerError, transaction::TransactionError, wallet::WalletError, KromerError}, models::{error::ErrorResponse, websockets::{OutgoingWebSocketMessage, ResponseMessageType, WebSocketMessageType, WsSessionModification}}, websockets::utils::datetime::convert_to_iso_string, invoices, AppState};
 
 use crate::database::models::transaction::Model as Transaction;
 use crate::database::models::wallet::Model as Wallet;
 
 pub async fn make_transaction(
-    db: &Arc<Surreal<Any>>,
+    state: &AppState,
     msg_id: String,
     private_key: Option<String>,
     to: Option<String>,
@@ -18,93 +16,101 @@ pub async fn make_transaction(
     metadata: Option<String>,
     _request_id: Option<String>,
 ) -> WsSessionModification {
-    let mut outgoing_message: OutgoingWebSocketMessage = OutgoingWebSocketMessage {
-        ok: Some(false),
-        id: msg_id.clone(),
-        message: WebSocketMessageType::Response { 
-            message: ResponseMessageType::Me { is_guest: true, address: None }
-        }
-    };
-
-    if amount.is_some() && private_key.is_some() && to.is_some() {
-        // Unwrap is fine because we checked it. 
-        let amount = amount.unwrap();
-        let private_key = private_key.unwrap();
-        let to = to.unwrap();
-
-        // Check on the server so DB doesnt throw.
-        if amount < dec!(0.0) {
-            outgoing_message = format_invalid_parameter(msg_id.clone(), "amount".to_string());
-        }
-
-        else if let Ok(Some(sender)) = Wallet::verify(db, private_key).await {
-            if let Ok(Some(recipient)) = Wallet::get_by_address(db, to.to_string()).await {
-                tracing::debug!("SENDER: {:?}", sender);
-                tracing::debug!("RECIPIENT: {:?}", recipient);
-                // Make sure to check the request to see if the funds are available.
-                if sender.balance < amount {
-                    outgoing_message = format_insufficient_funds_error(msg_id.clone())
-                } else {
-                // Create the data
-                let creation_data = TransactionCreateData {
-                    from: sender.id.unwrap(), // `unwrap` should be fine here, we already made sure it exists.
-                    to: recipient.id.unwrap(),
-                    amount: amount,
-                    metadata: metadata.clone(),
-                    transaction_type: TransactionType::Transfer,
-                };
-                let response = db.insert("transaction").content(creation_data).await;
-
-                if response.is_ok() {
-                    let _response: Vec<Transaction> = response.unwrap();
-
-                    let time = convert_to_iso_string(chrono::offset::Utc::now());
-                    outgoing_message = OutgoingWebSocketMessage {
-                        ok: Some(true),
-                        id: msg_id,
-                        message: WebSocketMessageType::Response {
-                            message: ResponseMessageType::MakeTransaction {
-                                from: sender.address,
-                                to: recipient.address,
-                                value: amount,
-                                time,
-                                name: None,
-                                metadata: metadata,
-                                sent_metaname: None,
-                                sent_name: None,
-                                transaction_type: "transfer".to_string(),
-                            }
-                         }
+    let db = &state.db;
+    let outgoing_message = match (private_key, to, amount) {
+        (Some(private_key), Some(to), Some(amount)) => {
+            // Check on the server so DB doesnt throw.
+            if amount < dec!(0.0) {
+                format_invalid_parameter(msg_id, "amount".to_string())
+            } else {
+                match transfer(db, private_key, &to, amount, metadata).await {
+                    Ok((sender, recipient, transaction)) => {
+                        invoices::settle_payment(state, &transaction).await;
+
+                        let time = convert_to_iso_string(chrono::offset::Utc::now());
+                        OutgoingWebSocketMessage {
+                            ok: Some(true),
+                            id: msg_id,
+                            message: WebSocketMessageType::Response {
+                                message: ResponseMessageType::MakeTransaction {
+                                    from: sender.address,
+                                    to: recipient.address,
+                                    value: transaction.amount,
+                                    time,
+                                    name: None,
+                                    metadata: transaction.metadata,
+                                    sent_metaname: None,
+                                    sent_name: None,
+                                    transaction_type: "transfer".to_string(),
+                                },
+                            },
+                        }
+                    }
+                    Err(KromerError::Wallet(WalletError::InvalidPassword)) => format_invalid_parameter(msg_id, "privatekey".to_string()),
+                    Err(KromerError::Wallet(WalletError::ApprovalRequired)) => format_approval_required_error(msg_id),
+                    Err(KromerError::Wallet(WalletError::NotFound)) => format_not_found_error(msg_id, to),
+                    Err(KromerError::Wallet(WalletError::Frozen(address))) => format_frozen_error(msg_id, address),
+                    Err(KromerError::Player(e)) => format_player_error(msg_id, e),
+                    Err(KromerError::Transaction(TransactionError::InsufficientFunds)) => format_insufficient_funds_error(msg_id),
+                    Err(e) => {
+                        tracing::error!("Failed to make transaction: {e}");
+                        format_database_error(msg_id)
                     }
-                } else {
-                    outgoing_message = format_database_error(msg_id);
-                }
-
-   
                 }
-
-            } else {
-                outgoing_message = format_not_found_error(msg_id, to);
             }
-        } else {
-            outgoing_message = format_invalid_parameter(msg_id, "privatekey".to_string())
         }
-    }
-    else if amount.is_none() {
-        outgoing_message = format_missing_parameter(msg_id, "amount".to_string());
-    }
-    else if private_key.is_none() {
-        outgoing_message = format_missing_parameter(msg_id, "privatekey".to_string());
-    }
-    else if to.is_none() {
-        outgoing_message = format_missing_parameter(msg_id, "to".to_string())
-    }
+        (_, _, None) => format_missing_parameter(msg_id, "amount".to_string()),
+        (None, _, _) => format_missing_parameter(msg_id, "privatekey".to_string()),
+        (_, None, _) => format_missing_parameter(msg_id, "to".to_string()),
+    };
+
     WsSessionModification {
         msg_type: Some(outgoing_message),
         wrapped_ws_data: None
     }
+}
 
+/// Transfer to `to`, which may be an address or `@PlayerName`, with the same checks as `POST /api/v1/transaction`.
+async fn transfer(
+    db: &Surreal<Any>,
+    private_key: String,
+    to: &str,
+    amount: Decimal,
+    metadata: Option<String>,
+) -> Result<(Wallet, Wallet, Transaction), KromerError> {
+    let recipient = Wallet::resolve_recipient(db, to).await?;
+    let sender = Wallet::authorize_sender(db, private_key, None, Some(&recipient), amount).await?;
+    let transaction = Transaction::transfer(
+        db,
+        &sender.wallet,
+        sender.spender.as_ref(),
+        &recipient,
+        amount,
+        metadata,
+    )
+    .await?;
+
+    Ok((sender.wallet, recipient, transaction))
+}
+
+fn format_player_error(msg_id: String, error: PlayerError) -> OutgoingWebSocketMessage {
+    let error_type = match error {
+        PlayerError::NotFound => "player_not_found",
+        PlayerError::NoWallet(_) => "player_has_no_wallet",
+        PlayerError::NoPrimaryWallet(_) => "player_has_no_primary_wallet",
+        PlayerError::WalletNotOwned(_) => "wallet_not_owned",
+    };
 
+    OutgoingWebSocketMessage {
+        ok: Some(false),
+        id: msg_id,
+        message: WebSocketMessageType::Error {
+            error: ErrorResponse {
+                error: error_type.to_string(),
+                message: Some(error.to_string()),
+            }
+        }
+    }
 }
 
 fn format_invalid_parameter(msg_id: String, parameter: String) -> OutgoingWebSocketMessage {
@@ -146,6 +152,32 @@ fn format_not_found_error(msg_id: String, address: String) -> OutgoingWebSocketM
     }
 }
 
+fn format_frozen_error(msg_id: String, address: String) -> OutgoingWebSocketMessage {
+    OutgoingWebSocketMessage {
+        ok: Some(false),
+        id: msg_id,
+        message: WebSocketMessageType::Error {
+            error: ErrorResponse {
+                error: "address_frozen".to_string(),
+                message: Some(format!("Address {} is frozen", address)),
+            }
+        }
+    }
+}
+
+fn format_approval_required_error(msg_id: String) -> OutgoingWebSocketMessage {
+    OutgoingWebSocketMessage {
+        ok: Some(false),
+        id: msg_id,
+        message: WebSocketMessageType::Error {
+            error: ErrorResponse {
+                error: "approval_required".to_string(),
+                message: Some("Transfer needs approval by the members of the wallet".to_string())
+            }
+        }
+    }
+}
+
 fn format_insufficient_funds_error(msg_id: String) -> OutgoingWebSocketMessage {
     OutgoingWebSocketMessage {
         ok: Some(false),
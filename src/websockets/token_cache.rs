@@ -30,6 +30,12 @@ impl TokenCache {
         }
     }
 
+    /// Remove all unused tokens issued for an address.
+    pub fn remove_address(&mut self, address: &str) {
+        self.token_cache
+            .retain(|_, token_params| token_params.address != address);
+    }
+
     pub fn remove_token(&mut self, uuid: Uuid) -> Option<WebSocketTokenData> {
         let token_data = self.token_cache.remove(&uuid);
         tracing::info!("Removed token {uuid} from cache");
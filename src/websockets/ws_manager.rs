@@ -3,6 +3,14 @@ use std::collections::HashMap;
 use surrealdb::Uuid;
 
 use super::wrapped_ws::WrappedWsData;
+use crate::AppState;
+
+/// Invalidate every websocket session and unused websocket token of an address, e.g. after its key changed.
+pub async fn invalidate_address(state: &AppState, address: &str) {
+    state.token_cache.lock().await.remove_address(address);
+    let count = state.ws_manager.lock().await.logout_address(address);
+    tracing::info!("Logged out {count} websocket sessions of {address}");
+}
 
 #[derive(Default, Debug)]
 pub struct WsDataManager {
@@ -37,4 +45,19 @@ impl WsDataManager {
     pub fn remove(&mut self, uuid: Uuid) {
         self.sockets.remove(&uuid);
     }
+
+    /// Log every session authenticated as the given address out, e.g. after its key changed.
+    /// Returns the amount of sessions that were logged out.
+    pub fn logout_address(&mut self, address: &str) -> usize {
+        let mut count = 0;
+        for socket in self.sockets.values_mut() {
+            if socket.address == address {
+                socket.address = "guest".to_string();
+                socket.privatekey = None;
+                count += 1;
+            }
+        }
+
+        count
+    }
 }